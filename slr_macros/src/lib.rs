@@ -0,0 +1,191 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! The `grammar!` macro: a grammar written inline in Rust source becomes
+//! its LALR(1) parser, built while the crate compiles.
+//!
+//!     #![feature(phase)]
+//!
+//!     #[phase(plugin)]
+//!     extern crate slr_macros;
+//!
+//!     mod expr {
+//!         #![allow(dead_code, non_camel_case_types, non_upper_case_globals)]
+//!
+//!         grammar! {
+//!             Expr -> Expr PLUS Term | Term ;
+//!             Term -> Term STAR Factor | Factor ;
+//!             Factor -> NUMBER | LPAREN Expr RPAREN ;
+//!         }
+//!     }
+//!
+//! The macro expands to the items `gen_rust` writes, `Token`, `NonTerm`,
+//! `parse` and the rest, so it is best given a module to itself. The grammar
+//! is read with the grammar file reader, from the tokens rustc hands the
+//! macro, so anything a grammar file may hold may be written, as long as
+//! rustc can tokenize it: use `//` and `/* */` comments rather than `#`.
+//! Errors in the grammar and conflicts in its table are reported at the
+//! place in the grammar they are about.
+
+#![crate_type = "dylib"]
+#![feature(plugin_registrar)]
+
+extern crate syntax;
+extern crate rustc;
+extern crate slr;
+
+use std::cmp;
+
+use syntax::ast;
+use syntax::codemap::{Span, BytePos, mk_sp};
+use syntax::ext::base::{ExtCtxt, MacResult, MacItems, DummyResult};
+use syntax::parse;
+use syntax::print::pprust;
+use rustc::plugin::Registry;
+
+use slr::{Grammar, Item, Position, Parse, Invalid};
+use slr::tables::Tables;
+
+#[plugin_registrar]
+pub fn plugin_registrar(reg : &mut Registry) {
+    reg.register_macro("grammar", expand_grammar);
+}
+
+fn expand_grammar(cx : &mut ExtCtxt, sp : Span, tts : &[ast::TokenTree]) -> Box<MacResult + 'static> {
+    let mut source = Source{text: String::new(), pieces: Vec::new()};
+    source.add_all(tts);
+    let text = source.text.as_slice();
+
+    let grammar = match Grammar::from_str(text) {
+        Ok(grammar) => { grammar }
+        Err(Parse(err)) => {
+            cx.span_err(source.span(sp, err.position(), err.len()), err.message().as_slice());
+            return DummyResult::any(sp)
+        }
+        Err(Invalid(diagnostics)) => {
+            for d in diagnostics.iter() {
+                report(cx, source.span(sp, d.position(), d.len()), d.is_error(), d.description());
+            }
+            return DummyResult::any(sp)
+        }
+    };
+    for d in grammar.validate().iter() {
+        report(cx, source.span(sp, d.position(), d.len()), d.is_error(), d.description());
+    }
+
+    let automaton = grammar.lr0_automaton();
+    let table = automaton.lalr_table();
+    if table.has_conflicts() {
+        for conflict in table.conflicts().iter() {
+            let message = format!("{}", conflict);
+            let located : Vec<(&Item, Position)> = conflict.items.iter().filter_map(|item| {
+                item.production().pos().map(|pos| (item, pos.clone()))
+            }).collect();
+            match located.as_slice().head() {
+                Some(&(_, ref pos)) => { cx.span_err(source.span(sp, Some(pos.clone()), 1), message.as_slice()); }
+                None => { cx.span_err(sp, message.as_slice()); }
+            }
+            for &(item, ref pos) in located.iter().skip(1) {
+                cx.span_note(source.span(sp, Some(pos.clone()), 1), format!("{}", item).as_slice());
+            }
+        }
+        return DummyResult::any(sp)
+    }
+
+    let tables = match Tables::new(&grammar, &table) {
+        Ok(tables) => { tables }
+        Err(err) => {
+            cx.span_err(sp, err.as_slice());
+            return DummyResult::any(sp)
+        }
+    };
+    let code = slr::gen_rust::items(&tables);
+    let mut parser = parse::new_parser_from_source_str(
+        cx.parse_sess(), cx.cfg(), "grammar!".to_string(), code);
+    let mut items = Vec::new();
+    loop {
+        match parser.parse_item(Vec::new()) {
+            Some(item) => { items.push(item); }
+            None => { break }
+        }
+    }
+    MacItems::new(items.into_iter())
+}
+
+/// The grammar text rebuilt from the macro's tokens, with the span each
+/// piece of it came from.
+struct Source {
+    text : String,
+    /// The offset in `text` of each token, its length there and its span.
+    pieces : Vec<(uint, uint, Span)>
+}
+
+impl Source {
+    fn add_all(&mut self, tts : &[ast::TokenTree]) {
+        for tt in tts.iter() {
+            match *tt {
+                ast::TtToken(sp, ref token) => { self.add(pprust::token_to_string(token), sp); }
+                ast::TtDelimited(_, ref delimited) => {
+                    self.add(pprust::token_to_string(&delimited.open_token()), delimited.open_span);
+                    self.add_all(delimited.tts.as_slice());
+                    self.add(pprust::token_to_string(&delimited.close_token()), delimited.close_span);
+                }
+                ast::TtSequence(sp, ..) => { self.add(pprust::tt_to_string(tt), sp); }
+            }
+        }
+    }
+
+    /// Appends a token, separated from the one before by a space unless the
+    /// two were adjacent in the source, so that `%` `left` stays `%left`.
+    fn add(&mut self, token : String, sp : Span) {
+        let adjacent = match self.pieces.last() {
+            Some(&(_, _, prev)) => { prev.hi == sp.lo }
+            None => { true }
+        };
+        if !adjacent {
+            self.text.push(' ');
+        }
+        self.pieces.push((self.text.len(), token.len(), sp));
+        self.text.push_str(token.as_slice());
+    }
+
+    /// The span of the `len` characters at `pos` in the text, or `sp`, the
+    /// whole invocation, when the position is unknown or not in a token.
+    fn span(&self, sp : Span, pos : Option<Position>, len : uint) -> Span {
+        let start = match pos {
+            Some(pos) => { pos.offset }
+            None => { return sp }
+        };
+        let bytes = self.text.as_slice().slice_from(start).char_indices().nth(len).map(|(i, _)| i);
+        let end = start + bytes.unwrap_or(self.text.len() - start);
+        match (self.locate(start, false), self.locate(end, true)) {
+            (Some(lo), Some(hi)) if lo <= hi => { mk_sp(lo, hi) }
+            (Some(lo), _) => { mk_sp(lo, lo) }
+            _ => { sp }
+        }
+    }
+
+    /// The source position of the byte at `offset` in the text, or just past
+    /// it when `end` is set.
+    fn locate(&self, offset : uint, end : bool) -> Option<BytePos> {
+        for &(at, len, sp) in self.pieces.iter() {
+            let inside = if end { at < offset && offset <= at + len } else { at <= offset && offset < at + len };
+            if inside {
+                let BytePos(lo) = sp.lo;
+                let BytePos(hi) = sp.hi;
+                return Some(BytePos(cmp::min(hi, lo + (offset - at) as u32)))
+            }
+        }
+        None
+    }
+}
+
+fn report(cx : &ExtCtxt, sp : Span, error : bool, message : String) {
+    if error {
+        cx.span_err(sp, message.as_slice());
+    } else {
+        cx.span_warn(sp, message.as_slice());
+    }
+}
@@ -0,0 +1,103 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! Compiles grammars from a cargo build script. With `build = "build.rs"`
+//! in the manifest and this `build.rs`
+//!
+//!     extern crate slr;
+//!
+//!     fn main() {
+//!         slr::build::process_grammar("src/expr.grammar");
+//!     }
+//!
+//! the crate gets the parser, as written by `gen_rust`, with
+//!
+//!     include!(concat!(env!("OUT_DIR"), "/expr.rs"))
+//!
+//! which defines `mod expr`.
+
+use std::io;
+use std::io::fs;
+use std::os;
+
+use slr::Grammar;
+use tables::{Tables, identifiers};
+use diagnostic;
+use gen_rust;
+
+/// Writes the LALR(1) parser for the grammar at `path` to `OUT_DIR` as a
+/// module named after the file, `expr` for `src/expr.grammar`, and returns
+/// where it was written. The parser is only written again when the grammar
+/// is newer than it.
+///
+/// Diagnostics go to standard error. Errors in the grammar and conflicts in
+/// its table fail the build; warnings do not.
+pub fn process_grammar(path : &str) -> Path {
+    let out_dir = match os::getenv("OUT_DIR") {
+        Some(dir) => { Path::new(dir) }
+        None => { fail!("OUT_DIR is not set, process_grammar must be run from a build script") }
+    };
+    let source = Path::new(path);
+    let name = match source.filestem_str() {
+        Some(name) => { identifiers(&[name.to_string()], &[]).pop().unwrap() }
+        None => { fail!("{} does not name a grammar file", path) }
+    };
+    let target = out_dir.join(format!("{}.rs", name));
+    if up_to_date(&source, &target) {
+        return target
+    }
+
+    let text = match io::File::open(&source).read_to_string() {
+        Ok(text) => { text }
+        Err(err) => { fail!("could not read {}: {}", path, err) }
+    };
+    let grammar = match Grammar::from_str(text.as_slice()) {
+        Ok(grammar) => { grammar }
+        Err(err) => {
+            report(err.format(path, text.as_slice()).as_slice());
+            fail!("{} is not a valid grammar", path)
+        }
+    };
+    for d in grammar.validate().iter() {
+        match d.position() {
+            Some(pos) => { report(diagnostic::format(path, text.as_slice(), &pos, d.len(), d.message().as_slice()).as_slice()); }
+            None => { report(format!("{}: {}", path, d.message()).as_slice()); }
+        }
+    }
+
+    let automaton = grammar.lr0_automaton();
+    let table = automaton.lalr_table();
+    if table.has_conflicts() {
+        for conflict in table.conflicts().iter() {
+            report(diagnostic::conflict(path, text.as_slice(), conflict).as_slice());
+        }
+        fail!("{} is not LALR(1): {} conflicts", path, table.conflicts().len())
+    }
+
+    let tables = match Tables::new(&grammar, &table) {
+        Ok(tables) => { tables }
+        Err(err) => { fail!("{}: {}", path, err) }
+    };
+    let code = gen_rust::generate(&tables, path);
+    let module = format!("pub mod {} {{\n{}}}\n", name, code);
+    match io::File::create(&target).write_str(module.as_slice()) {
+        Ok(()) => {}
+        Err(err) => { fail!("could not write {}: {}", target.display(), err) }
+    }
+    target
+}
+
+/// Whether `target` was written after `source` was last changed.
+fn up_to_date(source : &Path, target : &Path) -> bool {
+    match (fs::stat(source), fs::stat(target)) {
+        (Ok(s), Ok(t)) => { t.modified >= s.modified }
+        _ => { false }
+    }
+}
+
+fn report(message : &str) {
+    let mut stderr = io::stderr();
+    stderr.write_line(message).ok();
+}
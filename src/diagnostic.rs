@@ -0,0 +1,51 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+use gram_lexer::Position;
+use slr::Conflict;
+
+/// Formats a message about a place in a grammar file the way compilers do:
+///
+///     expr.grammar:12:7: expected ';' but got '|'
+///           | Term
+///           ^
+///
+/// The line is quoted from `text` and the `len` characters starting at `pos`
+/// are underlined.
+pub fn format(path : &str, text : &str, pos : &Position, len : uint, message : &str) -> String {
+    let mut out = format!("{}:{}:{}: {}\n", path, pos.line, pos.column, message);
+    let line = match text.lines().nth(pos.line - 1) {
+        Some(line) => { line }
+        None => { return out }
+    };
+    out.push_str(line);
+    out.push_str("\n");
+    for ch in line.chars().take(pos.column - 1) {
+        // keep tabs so the caret lines up with the quoted line
+        out.push_str(if ch == '\t' { "\t" } else { " " });
+    }
+    out.push_str("^");
+    for _ in range(1, len) {
+        out.push_str("~");
+    }
+    out
+}
+
+/// A conflict followed by a note at each production involved in it, for the
+/// productions whose place in `text` is known.
+pub fn conflict(path : &str, text : &str, conflict : &Conflict) -> String {
+    let mut out = format!("{}", conflict);
+    for item in conflict.items.iter() {
+        match item.production().pos() {
+            Some(pos) => {
+                let note = format!("note: {}", item);
+                out.push_str(format(path, text, pos, 1, note.as_slice()).as_slice());
+                out.push_str("\n");
+            }
+            None => {}
+        }
+    }
+    out
+}
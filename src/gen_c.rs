@@ -0,0 +1,240 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! Writes a parser as a C header and source file. The parser is push style:
+//! the caller hands it one token at a time. All of its state, stacks
+//! included, lives in memory the caller provides, so it is reentrant and
+//! never allocates. Every name starts with a prefix, `expr_` and `EXPR_` for
+//! a parser written to `expr.h` and `expr.c`.
+
+use std::ascii::StrAsciiExt;
+
+use tables::{Tables, ACCEPT, identifiers};
+
+static HEADER : &'static str = r#"
+#include <stddef.h>
+#include <stdint.h>
+
+/* The status of a parser, returned by $p_push. */
+#define $P_MORE 0      /* give it the next token */
+#define $P_ACCEPT 1    /* the input was accepted, see result */
+#define $P_ERROR (-1)  /* a syntax error */
+#define $P_OVERFLOW (-2) /* the stacks given to $p_init are full */
+
+/* Called on every reduction with the number of the production, its
+ * nonterminal and the values of its right hand side. Returns the value of
+ * the nonterminal. */
+typedef void *(*$p_reduce_fn)(void *ctx, int production, int nonterm, void **kids, int nkids);
+
+struct $p_parser {
+    int *states;
+    void **values;
+    size_t depth;
+    size_t capacity;
+    size_t position;
+    $p_reduce_fn reduce;
+    void *ctx;
+    int status;
+    void *result;
+};
+
+extern const char *const $p_token_names[];
+extern const char *const $p_nonterm_names[];
+extern const char *const $p_productions[];
+
+/* Starts a parse from `start`, one of the $P_START_ constants. `states` and
+ * `values` must both hold `capacity` entries; they bound the nesting depth
+ * of the input. */
+void $p_init(struct $p_parser *p, int start, int *states, void **values, size_t capacity, $p_reduce_fn reduce, void *ctx);
+
+/* Gives the parser the next token and its value, $P_EOF at the end of the
+ * input. Returns the parser's status. */
+int $p_push(struct $p_parser *p, int token, void *value);
+
+/* Fills `tokens` with up to `max` of the tokens the parser would accept
+ * next and returns how many there are in all. */
+int $p_expected(const struct $p_parser *p, int *tokens, int max);
+"#;
+
+static DRIVER : &'static str = r#"
+static int32_t $p_action(int state, int token) {
+    return $p_action_table[$p_action_row[state] * $P_COLUMNS + token];
+}
+
+void $p_init(struct $p_parser *p, int start, int *states, void **values, size_t capacity, $p_reduce_fn reduce, void *ctx) {
+    p->states = states;
+    p->values = values;
+    p->capacity = capacity;
+    p->position = 0;
+    p->reduce = reduce;
+    p->ctx = ctx;
+    p->result = NULL;
+    if (capacity == 0) {
+        p->depth = 0;
+        p->status = $P_OVERFLOW;
+        return;
+    }
+    p->states[0] = start;
+    p->values[0] = NULL;
+    p->depth = 1;
+    p->status = $P_MORE;
+}
+
+int $p_push(struct $p_parser *p, int token, void *value) {
+    if (p->status != $P_MORE) {
+        return p->status;
+    }
+    for (;;) {
+        int state = p->states[p->depth - 1];
+        int32_t act = $p_action(state, token);
+        if (act == $P_ACTION_ACCEPT) {
+            p->result = p->values[p->depth - 1];
+            return p->status = $P_ACCEPT;
+        } else if (act > 0) {
+            if (p->depth == p->capacity) {
+                return p->status = $P_OVERFLOW;
+            }
+            p->states[p->depth] = act - 1;
+            p->values[p->depth] = value;
+            p->depth++;
+            p->position++;
+            return $P_MORE;
+        } else if (act < 0) {
+            int production = -act - 1;
+            int nkids = $p_rhs_len[production];
+            int nonterm = $p_lhs[production];
+            void *result = p->reduce(p->ctx, production, nonterm, &p->values[p->depth - nkids], nkids);
+            p->depth -= nkids;
+            if (p->depth == p->capacity) {
+                return p->status = $P_OVERFLOW;
+            }
+            state = p->states[p->depth - 1];
+            p->states[p->depth] = $p_goto_table[$p_goto_row[state] * $P_NONTERMS + nonterm];
+            p->values[p->depth] = result;
+            p->depth++;
+        } else {
+            return p->status = $P_ERROR;
+        }
+    }
+}
+
+int $p_expected(const struct $p_parser *p, int *tokens, int max) {
+    int state = p->states[p->depth - 1];
+    int count = 0;
+    int token;
+    for (token = 0; token < $P_COLUMNS; token++) {
+        if ($p_action(state, token) != 0) {
+            if (count < max) {
+                tokens[count] = token;
+            }
+            count++;
+        }
+    }
+    return count;
+}
+"#;
+
+/// The header and source files for `tables`. `name` is the file name the
+/// header is written to, without `.h`, and gives the prefix; `source` names
+/// the grammar in the header comment.
+pub fn generate(tables : &Tables, source : &str, name : &str) -> (String, String) {
+    let prefix = identifiers(&[name.to_string()], &[]).pop().unwrap().as_slice().to_ascii_lower();
+    let upper = prefix.as_slice().to_ascii_upper();
+    // the names of the macros, which share the prefix with the tokens
+    let terminals = identifiers(tables.terminals.as_slice(), &[
+        "EOF", "MORE", "ACCEPT", "ERROR", "OVERFLOW", "H", "COLUMNS", "NONTERMS", "ACTION_ACCEPT"
+    ]);
+    let nonterminals = identifiers(tables.nonterminals.as_slice(), &[]);
+
+    let mut h = String::new();
+    h.push_str(format!("/* Generated by slr from {}. Do not edit. */\n\n", source).as_slice());
+    h.push_str(format!("#ifndef {}_H\n#define {}_H\n", upper, upper).as_slice());
+    h.push_str(HEADER.replace("$P", upper.as_slice()).replace("$p", prefix.as_slice()).as_slice());
+    h.push_str("\n/* The tokens, numbered as the columns of the action table. */\n");
+    h.push_str(format!("enum {}_token {{\n    {}_EOF = 0", prefix, upper).as_slice());
+    for (i, ident) in terminals.iter().enumerate() {
+        h.push_str(format!(",\n    {}_{} = {}", upper, ident, i + 1).as_slice());
+    }
+    h.push_str("\n};\n\n");
+    h.push_str(format!("enum {}_nonterm {{", prefix).as_slice());
+    for (i, ident) in nonterminals.iter().enumerate() {
+        h.push_str(format!("{}\n    {}_NT_{} = {}", if i == 0 { "" } else { "," }, upper, ident, i).as_slice());
+    }
+    h.push_str("\n};\n\n");
+    h.push_str("/* The initial state of each start symbol, for init. */\n");
+    for &(ref start, state) in tables.starts.iter() {
+        let i = tables.nonterminals.iter().position(|n| n == start).unwrap();
+        h.push_str(format!("#define {}_START_{} {}\n", upper, nonterminals[i], state).as_slice());
+    }
+    h.push_str("\n#endif\n");
+
+    let mut c = String::new();
+    c.push_str(format!("/* Generated by slr from {}. Do not edit. */\n\n", source).as_slice());
+    c.push_str(format!("#include \"{}.h\"\n\n", name).as_slice());
+    c.push_str(format!("#define {}_COLUMNS {}\n", upper, tables.columns()).as_slice());
+    c.push_str(format!("#define {}_NONTERMS {}\n", upper, tables.nonterminals.len()).as_slice());
+    c.push_str(format!("#define {}_ACTION_ACCEPT {}\n\n", upper, ACCEPT).as_slice());
+
+    let mut names = vec![quote("$end")];
+    names.extend(tables.terminals.iter().map(|t| quote(t.as_slice())));
+    array(&mut c, format!("const char *const {}_token_names", prefix).as_slice(), &names);
+    array(&mut c, format!("const char *const {}_nonterm_names", prefix).as_slice(),
+          &tables.nonterminals.iter().map(|n| quote(n.as_slice())).collect());
+    array(&mut c, format!("const char *const {}_productions", prefix).as_slice(),
+          &tables.productions.iter().map(|p| quote(p.to_string().as_slice())).collect());
+    array(&mut c, format!("static const uint16_t {}_lhs", prefix).as_slice(),
+          &tables.lhs.iter().map(|n| n.to_string()).collect());
+    array(&mut c, format!("static const uint16_t {}_rhs_len", prefix).as_slice(),
+          &tables.productions.iter().map(|p| p.symbols().len().to_string()).collect());
+    array(&mut c, format!("static const uint16_t {}_action_row", prefix).as_slice(),
+          &tables.action_rows.iter().map(|r| r.to_string()).collect());
+    array(&mut c, format!("static const int32_t {}_action_table", prefix).as_slice(), &tables.action_values());
+    array(&mut c, format!("static const uint16_t {}_goto_row", prefix).as_slice(),
+          &tables.goto_rows.iter().map(|r| r.to_string()).collect());
+    array(&mut c, format!("static const int32_t {}_goto_table", prefix).as_slice(), &tables.goto_values());
+    c.push_str(DRIVER.replace("$P", upper.as_slice()).replace("$p", prefix.as_slice()).as_slice());
+    (h, c)
+}
+
+/// Writes `decl[] = { ... };`, a few values to a line. C does not allow
+/// empty initializers, so an empty array gets a single 0.
+fn array(out : &mut String, decl : &str, values : &Vec<String>) {
+    out.push_str(format!("{}[] = {{", decl).as_slice());
+    if values.len() == 0 {
+        out.push_str(" 0 };\n\n");
+        return
+    }
+    for (i, value) in values.iter().enumerate() {
+        out.push_str(if i % 12 == 0 { "\n    " } else { " " });
+        out.push_str(value.as_slice());
+        if i + 1 < values.len() {
+            out.push_str(",");
+        }
+    }
+    out.push_str("\n};\n\n");
+}
+
+/// A C string literal for `text`.
+fn quote(text : &str) -> String {
+    let mut out = String::from_str("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => { out.push_str("\\\""); }
+            '\\' => { out.push_str("\\\\"); }
+            '\n' => { out.push_str("\\n"); }
+            '\t' => { out.push_str("\\t"); }
+            ' ' .. '~' => { out.push(ch); }
+            _ => {
+                let mut buf = [0u8, ..4];
+                let n = ch.encode_utf8(&mut buf).unwrap_or(0);
+                for b in buf.slice_to(n).iter() {
+                    out.push_str(format!("\\{:03o}", *b).as_slice());
+                }
+            }
+        }
+    }
+    out.push_str("\"");
+    out
+}
@@ -0,0 +1,111 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! Draws an LR(0) automaton as a Graphviz digraph, for `dot -Tsvg`. Each
+//! state is a node listing its items, the kernel items in bold above the
+//! ones added by the closure, and each move is an edge labeled with its
+//! symbol. States with a conflict in the table are drawn in red.
+
+use std::collections::HashSet;
+
+use slr::{Automaton, SLRAutomaton, LRTable, Item, Symbol, Term, NonTerm, EmptyString, EndOfInput};
+
+/// The digraph for `automaton`, with the conflicts of `table`, which must
+/// have been read off it. `name` names the graph.
+pub fn generate(automaton : &SLRAutomaton, table : &LRTable, name : &str) -> String {
+    let grammar = automaton.grammar();
+    let conflicted : HashSet<uint> = table.conflicts().iter().map(|c| c.state).collect();
+
+    let mut out = String::new();
+    out.push_str(format!("digraph \"{}\" {{\n", quote(name)).as_slice());
+    out.push_str("    node [shape=plaintext, fontname=\"Helvetica\"];\n");
+    out.push_str("    edge [fontname=\"Helvetica\"];\n\n");
+    for state in automaton.states().iter() {
+        let id = state.id();
+        let conflict = conflicted.contains(&id);
+        let mut title = format!("State {}", id);
+        for &(ref start, initial) in table.starts().iter() {
+            if initial == id {
+                title.push_str(format!(" (start {})", html(start.as_slice())).as_slice());
+            }
+        }
+        if conflict {
+            title.push_str(" (conflict)");
+        }
+        out.push_str(format!("    s{} [{}label=<\n", id, if conflict { "color=\"red\", " } else { "" }).as_slice());
+        out.push_str("        <table border=\"1\" cellborder=\"0\" cellspacing=\"0\">\n");
+        out.push_str(format!("        <tr><td bgcolor=\"{}\"><b>{}</b></td></tr>\n",
+                             if conflict { "salmon" } else { "lightgrey" }, title).as_slice());
+        for item in state.items().iter().filter(|item| grammar.is_kernel(*item)) {
+            out.push_str(format!("        <tr><td align=\"left\"><b>{}</b></td></tr>\n", html(item_label(item).as_slice())).as_slice());
+        }
+        for item in state.items().iter().filter(|item| !grammar.is_kernel(*item)) {
+            out.push_str(format!("        <tr><td align=\"left\">{}</td></tr>\n", html(item_label(item).as_slice())).as_slice());
+        }
+        out.push_str("        </table>>];\n");
+    }
+    out.push_str("\n");
+    for state in automaton.states().iter() {
+        let mut moves : Vec<(&Symbol, &uint)> = state.moves().iter().collect();
+        moves.sort();
+        for &(sym, target) in moves.iter() {
+            out.push_str(format!("    s{} -> s{} [label=\"{}\"];\n", state.id(), target, quote(symbol_label(sym).as_slice())).as_slice());
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// `A -> a . B b`, with the symbols written as in the grammar.
+fn item_label(item : &Item) -> String {
+    let mut label = format!("{} ->", item.production().nt());
+    for (i, sym) in item.production().symbols().iter().enumerate() {
+        if i == item.dot() {
+            label.push_str(" .");
+        }
+        label.push_str(" ");
+        label.push_str(symbol_label(sym).as_slice());
+    }
+    if item.dot() == item.production().symbols().len() {
+        label.push_str(" .");
+    }
+    label
+}
+
+fn symbol_label(sym : &Symbol) -> String {
+    match *sym {
+        Term(ref name) | NonTerm(ref name) => { name.clone() }
+        EmptyString => { "%empty".to_string() }
+        EndOfInput => { "$".to_string() }
+    }
+}
+
+/// `text` escaped for an HTML-like label.
+fn html(text : &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match ch {
+            '&' => { out.push_str("&amp;"); }
+            '<' => { out.push_str("&lt;"); }
+            '>' => { out.push_str("&gt;"); }
+            '"' => { out.push_str("&quot;"); }
+            _ => { out.push(ch); }
+        }
+    }
+    out
+}
+
+/// `text` escaped for a quoted DOT string.
+fn quote(text : &str) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match ch {
+            '"' => { out.push_str("\\\""); }
+            '\\' => { out.push_str("\\\\"); }
+            _ => { out.push(ch); }
+        }
+    }
+    out
+}
@@ -0,0 +1,212 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! Writes a parser as a Rust module which depends only on the standard
+//! library. The module has a `Token` enum of the terminals, a `NonTerm` enum
+//! of the nonterminals, the tables of `tables::Tables` as static arrays and a
+//! `parse_<start>` function for each start symbol, `parse` being the first.
+
+use tables::{Tables, ACCEPT, identifiers};
+
+/// Names the module defines or uses unqualified, which the enum variants
+/// must not take: the variants share the module's namespace with its
+/// statics and functions.
+static RESERVED : &'static [&'static str] = &[
+    "Token", "NonTerm", "SyntaxError", "Some", "None", "Ok", "Err", "Option",
+    "Result", "Vec", "String", "Iterator",
+    "TOKENS", "NONTERMS", "PRODUCTIONS", "LHS", "RHS_LEN", "ACCEPT", "COLUMNS",
+    "ACTION_ROW", "ACTION", "GOTO_ROW", "GOTO",
+    "action", "goto", "expected", "run", "parse"
+];
+
+/// The table driven parser, the same for every grammar.
+static DRIVER : &'static str = r#"
+/// Where the input stopped making sense: the number of tokens read before
+/// it, the token found (`None` at the end of the input) and the tokens which
+/// would have been accepted instead.
+#[deriving(Show, Clone)]
+pub struct SyntaxError {
+    pub position : uint,
+    pub got : Option<Token>,
+    pub expected : Vec<Option<Token>>
+}
+
+fn action(state : uint, column : uint) -> i32 {
+    ACTION[ACTION_ROW[state] as uint * COLUMNS + column]
+}
+
+fn goto(state : uint, nonterm : uint) -> uint {
+    GOTO[GOTO_ROW[state] as uint * NONTERMS.len() + nonterm] as uint
+}
+
+fn expected(state : uint) -> Vec<Option<Token>> {
+    let mut expected = Vec::new();
+    for column in range(0, COLUMNS) {
+        if action(state, column) != 0 {
+            expected.push(if column == 0 { None } else { Some(TOKENS[column - 1].clone()) });
+        }
+    }
+    expected
+}
+
+/// Runs the parser from `initial` over `tokens`, each a terminal and its
+/// value. `reduce` is called with the number of the production reduced by,
+/// its nonterminal and the values of its right hand side, and gives the
+/// value of the nonterminal. The value of the start symbol is returned.
+fn run<V, I : Iterator<(Token, V)>>(initial : uint, mut tokens : I, mut reduce : |uint, NonTerm, Vec<V>| -> V) -> Result<V, SyntaxError> {
+    let mut states : Vec<uint> = vec![initial];
+    let mut values : Vec<V> = Vec::new();
+    let mut position : uint = 0;
+    let mut lookahead : Option<(Token, V)> = tokens.next();
+    loop {
+        let state = *states.last().unwrap();
+        let column = match lookahead {
+            Some((ref token, _)) => { token.clone() as uint + 1 }
+            None => { 0 }
+        };
+        let act = action(state, column);
+        if act == ACCEPT {
+            return Ok(values.pop().unwrap())
+        } else if act > 0 {
+            match lookahead {
+                Some((_, value)) => { values.push(value); }
+                None => { fail!("shifted the end of the input") }
+            }
+            lookahead = tokens.next();
+            states.push((act - 1) as uint);
+            position += 1;
+        } else if act < 0 {
+            let production = (-act - 1) as uint;
+            let mut kids : Vec<V> = Vec::new();
+            for _ in range(0, RHS_LEN[production] as uint) {
+                states.pop();
+                kids.push(values.pop().unwrap());
+            }
+            kids.reverse();
+            let nonterm = LHS[production] as uint;
+            values.push(reduce(production, NONTERMS[nonterm].clone(), kids));
+            let top = *states.last().unwrap();
+            states.push(goto(top, nonterm));
+        } else {
+            return Err(SyntaxError{
+                position: position,
+                got: lookahead.map(|(token, _)| token),
+                expected: expected(state)
+            })
+        }
+    }
+}
+"#;
+
+/// The Rust module for `tables`. `source` names the grammar in the header
+/// comment.
+pub fn generate(tables : &Tables, source : &str) -> String {
+    let mut out = String::new();
+    out.push_str(format!("// Generated by slr from {}. Do not edit.\n\n", source).as_slice());
+    out.push_str("#![allow(dead_code, non_camel_case_types, non_upper_case_globals)]\n\n");
+    out.push_str(items(tables).as_slice());
+    out
+}
+
+/// The items of the module, without the header comment and attributes of
+/// `generate`, for splicing into a module of one's own.
+pub fn items(tables : &Tables) -> String {
+    let terminals = identifiers(tables.terminals.as_slice(), RESERVED);
+    let mut reserved : Vec<&str> = RESERVED.to_vec();
+    reserved.extend(terminals.iter().map(|t| t.as_slice()));
+    let nonterminals = identifiers(tables.nonterminals.as_slice(), reserved.as_slice());
+
+    let mut out = String::new();
+    out.push_str("#[deriving(Show, Clone, PartialEq, Eq)]\npub enum Token {\n");
+    for (ident, name) in terminals.iter().zip(tables.terminals.iter()) {
+        comment_if_renamed(&mut out, ident, name);
+        out.push_str(format!("    {},\n", ident).as_slice());
+    }
+    out.push_str("}\n\n");
+
+    out.push_str("#[deriving(Show, Clone, PartialEq, Eq)]\npub enum NonTerm {\n");
+    for (ident, name) in nonterminals.iter().zip(tables.nonterminals.iter()) {
+        comment_if_renamed(&mut out, ident, name);
+        out.push_str(format!("    {},\n", ident).as_slice());
+    }
+    out.push_str("}\n\n");
+
+    array(&mut out, "TOKENS", "Token", &terminals);
+    array(&mut out, "NONTERMS", "NonTerm", &nonterminals);
+
+    let productions : Vec<String> = tables.productions.iter().map(|p| {
+        format!("\"{}\"", p.to_string().as_slice().escape_default())
+    }).collect();
+    out.push_str("/// The productions, by the number passed to `reduce`.\npub ");
+    array(&mut out, "PRODUCTIONS", "&'static str", &productions);
+    array(&mut out, "LHS", "u16", &tables.lhs.iter().map(|n| n.to_string()).collect());
+    array(&mut out, "RHS_LEN", "u16", &tables.productions.iter().map(|p| p.symbols().len().to_string()).collect());
+
+    out.push_str(format!("static ACCEPT : i32 = {};\n", ACCEPT).as_slice());
+    out.push_str(format!("static COLUMNS : uint = {};\n\n", tables.columns()).as_slice());
+    array(&mut out, "ACTION_ROW", "u16", &tables.action_rows.iter().map(|r| r.to_string()).collect());
+    array(&mut out, "ACTION", "i32", &tables.action_values());
+    array(&mut out, "GOTO_ROW", "u16", &tables.goto_rows.iter().map(|r| r.to_string()).collect());
+    array(&mut out, "GOTO", "i32", &tables.goto_values());
+
+    out.push_str(DRIVER);
+
+    let names : Vec<String> = tables.starts.iter().map(|&(ref start, _)| {
+        let i = tables.nonterminals.iter().position(|name| name == start).unwrap();
+        format!("parse_{}", snake(nonterminals[i].as_slice()))
+    }).collect();
+    reserved.extend(nonterminals.iter().map(|n| n.as_slice()));
+    let names = identifiers(names.as_slice(), reserved.as_slice());
+    for (k, (&(ref start, state), name)) in tables.starts.iter().zip(names.iter()).enumerate() {
+        if k == 0 {
+            function(&mut out, "parse", start.as_slice(), state);
+        }
+        function(&mut out, name.as_slice(), start.as_slice(), state);
+    }
+    out
+}
+
+fn comment_if_renamed(out : &mut String, ident : &String, name : &String) {
+    if ident != name {
+        out.push_str(format!("    // {}\n", name.as_slice().escape_default()).as_slice());
+    }
+}
+
+fn function(out : &mut String, name : &str, start : &str, state : uint) {
+    out.push_str(format!("\n/// Parses a `{}`, see `run`.\n", start).as_slice());
+    out.push_str(format!("pub fn {}<V, I : Iterator<(Token, V)>>(tokens : I, reduce : |uint, NonTerm, Vec<V>| -> V) -> Result<V, SyntaxError> {{\n", name).as_slice());
+    out.push_str(format!("    run({}, tokens, reduce)\n}}\n", state).as_slice());
+}
+
+/// Writes `static NAME : [ty, ..N] = [...];`, a few values to a line.
+fn array(out : &mut String, name : &str, ty : &str, values : &Vec<String>) {
+    out.push_str(format!("static {} : [{}, ..{}] = [", name, ty, values.len()).as_slice());
+    for (i, value) in values.iter().enumerate() {
+        if i % 12 == 0 {
+            out.push_str("\n    ");
+        } else {
+            out.push_str(" ");
+        }
+        out.push_str(value.as_slice());
+        if i + 1 < values.len() {
+            out.push_str(",");
+        }
+    }
+    out.push_str("\n];\n\n");
+}
+
+/// `Expr` as `expr`, `ArgList` as `arg_list`.
+fn snake(ident : &str) -> String {
+    let mut out = String::new();
+    let mut prev_lower = false;
+    for ch in ident.chars() {
+        if ch.is_uppercase() && prev_lower {
+            out.push('_');
+        }
+        prev_lower = ch.is_lowercase() || ch.is_digit();
+        out.push(ch.to_lowercase());
+    }
+    out
+}
@@ -6,33 +6,124 @@
 use std::iter::Iterator;
 
 #[deriving(Show)]
-#[deriving(PartialEq)]
+#[deriving(PartialEq, Clone)]
 pub enum TokenType {
     TERM,
     NONTERM,
     SEMI,
     VBAR,
     ARROW,
-    EMPTY
+    EMPTY,
+    ACTION,
+    LEFT,
+    RIGHT,
+    NONASSOC,
+    PREC,
+    TOKEN,
+    LITERAL,
+    QUESTION,
+    STAR,
+    PLUS,
+    LPAREN,
+    RPAREN,
+    START
 }
 
-#[deriving(Show)]
+impl TokenType {
+    /// How the token type is named in error messages.
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            TERM => { "a terminal" }
+            NONTERM => { "a nonterminal" }
+            SEMI => { "';'" }
+            VBAR => { "'|'" }
+            ARROW => { "'->'" }
+            EMPTY => { "'%empty'" }
+            ACTION => { "an action" }
+            LEFT => { "'%left'" }
+            RIGHT => { "'%right'" }
+            NONASSOC => { "'%nonassoc'" }
+            PREC => { "'%prec'" }
+            TOKEN => { "'%token'" }
+            LITERAL => { "a quoted literal" }
+            QUESTION => { "'?'" }
+            STAR => { "'*'" }
+            PLUS => { "'+'" }
+            LPAREN => { "'('" }
+            RPAREN => { "')'" }
+            START => { "'%start'" }
+        }
+    }
+}
+
+/// A location in the grammar text. `offset` is in bytes, `line` and
+/// `column` count from 1 and `column` is in characters.
+#[deriving(Show, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub offset : uint,
+    pub line : uint,
+    pub column : uint
+}
+
+#[deriving(Show, Clone)]
 pub enum LexError {
-    UnexpectedCharacter(char),
-    BadState(uint),
+    UnexpectedCharacter(char, Position),
+    BadState(uint, Position),
+    UnterminatedAction(Position),
+    UnknownDirective(String, Position),
+    UnterminatedComment(Position),
+    UnterminatedLiteral(Position),
+}
+
+impl LexError {
+    pub fn position(&self) -> Position {
+        match *self {
+            UnexpectedCharacter(_, ref pos) => { pos.clone() }
+            BadState(_, ref pos) => { pos.clone() }
+            UnterminatedAction(ref pos) => { pos.clone() }
+            UnknownDirective(_, ref pos) => { pos.clone() }
+            UnterminatedComment(ref pos) => { pos.clone() }
+            UnterminatedLiteral(ref pos) => { pos.clone() }
+        }
+    }
+
+    /// The length of the text the error is about, for underlining.
+    pub fn len(&self) -> uint {
+        match *self {
+            UnknownDirective(ref directive, _) => { directive.as_slice().char_len() }
+            _ => { 1 }
+        }
+    }
 
+    pub fn message(&self) -> String {
+        match *self {
+            UnexpectedCharacter(ch, _) => { format!("unexpected character '{}'", ch) }
+            BadState(state, _) => { format!("lexer reached bad state {}", state) }
+            UnterminatedAction(_) => { "unterminated action".to_string() }
+            UnknownDirective(ref directive, _) => { format!("unknown directive {}", directive) }
+            UnterminatedComment(_) => { "unterminated block comment".to_string() }
+            UnterminatedLiteral(_) => { "unterminated quoted literal".to_string() }
+        }
+    }
 }
 
 #[deriving(Show)]
 pub struct Token<'a> {
     pub token : TokenType,
     pub lexeme : &'a str,
+    pub pos : Position,
 }
 
+/// Tokenizes grammar files. Whitespace and comments separate tokens and are
+/// otherwise ignored. Line comments start with `#` or `//`, block comments
+/// are written `/* ... */` and may be nested, so a block comment can be used
+/// to comment out a part of a grammar which already contains one.
 #[deriving(Show)]
 pub struct Lexer<'a> {
     text : &'a str,
     tc : uint,
+    line : uint,
+    line_tc : uint,
     failed : bool
 }
 
@@ -40,13 +131,15 @@ pub fn gram_lexer<'a>(text : &'a str) -> Lexer<'a> {
     return Lexer{
         text: text,
         tc: 0,
+        line: 1,
+        line_tc: 0,
         failed: false,
     };
 }
 
 impl<'a> Lexer<'a> {
     fn white(ch : char) -> bool {
-        ch == ' ' || ch == '\n' || ch == '\t'
+        ch == ' ' || ch == '\n' || ch == '\t' || ch == '\r'
     }
 
     fn big(ch : char) -> bool {
@@ -60,6 +153,84 @@ impl<'a> Lexer<'a> {
     fn alpha_num(ch : char) -> bool {
         ('a' <= ch && ch <= 'z') || ('A' <= ch && ch <= 'Z') || ('0' <= ch && ch <= '9') || ch == '_' || ch == '\''
     }
+
+    fn small(ch : char) -> bool {
+        'a' <= ch && ch <= 'z'
+    }
+
+    fn directive(lexeme : &str) -> Option<TokenType> {
+        match lexeme {
+            "%left" => { Some(LEFT) }
+            "%right" => { Some(RIGHT) }
+            "%nonassoc" => { Some(NONASSOC) }
+            "%prec" => { Some(PREC) }
+            "%start" => { Some(START) }
+            "%token" => { Some(TOKEN) }
+            "%empty" => { Some(EMPTY) }
+            _ => { None }
+        }
+    }
+
+    /// The line number and the offset of the start of that line for an
+    /// offset at or after the lexer's current offset.
+    fn scan(&self, offset : uint) -> (uint, uint) {
+        let mut line = self.line;
+        let mut line_tc = self.line_tc;
+        let mut tc = self.tc;
+        while tc < offset {
+            let ch_range = self.text.char_range_at(tc);
+            if ch_range.ch == '\n' {
+                line += 1;
+                line_tc = ch_range.next;
+            }
+            tc = ch_range.next;
+        }
+        (line, line_tc)
+    }
+
+    /// The position just past the end of the text.
+    pub fn end(&self) -> Position {
+        self.position(self.text.len())
+    }
+
+    /// The position of an offset at or after the lexer's current offset.
+    pub fn position(&self, offset : uint) -> Position {
+        let (line, line_tc) = self.scan(offset);
+        Position{
+            offset: offset,
+            line: line,
+            column: self.text.slice(line_tc, offset).char_len() + 1
+        }
+    }
+
+    fn advance(&mut self, offset : uint) {
+        let (line, line_tc) = self.scan(offset);
+        self.line = line;
+        self.line_tc = line_tc;
+        self.tc = offset;
+    }
+
+    fn token(&mut self, token : TokenType, start_tc : uint, end_tc : uint) -> Option<Result<Token<'a>,LexError>> {
+        let pos = self.position(start_tc);
+        self.advance(end_tc);
+        Some(Ok(Token{token:token,lexeme:self.text.slice(start_tc,end_tc),pos:pos}))
+    }
+
+    fn error(&mut self, err : LexError) -> Option<Result<Token<'a>,LexError>> {
+        self.failed = true;
+        Some(Err(err))
+    }
+
+    fn directive_token(&mut self, start_tc : uint, tc : uint) -> Option<Result<Token<'a>,LexError>> {
+        let lexeme = self.text.slice(start_tc, tc);
+        match Lexer::directive(lexeme) {
+            Some(token) => { self.token(token, start_tc, tc) }
+            None => {
+                let pos = self.position(start_tc);
+                self.error(UnknownDirective(lexeme.to_string(), pos))
+            }
+        }
+    }
 }
 
 impl<'a> Iterator<Result<Token<'a>,LexError>> for Lexer<'a> {
@@ -71,6 +242,7 @@ impl<'a> Iterator<Result<Token<'a>,LexError>> for Lexer<'a> {
         let mut state = 1;
         let mut start_tc = self.tc;
         let mut tc = self.tc;
+        let mut depth : uint = 0;
         while tc < self.text.len() {
             let ch_range = text.char_range_at(tc);
             let ch = ch_range.ch;
@@ -80,18 +252,37 @@ impl<'a> Iterator<Result<Token<'a>,LexError>> for Lexer<'a> {
                     if Lexer::white(ch) {
                         2
                     } else if ch == ';' {
-                        3
+                        return self.token(SEMI, start_tc, next_tc)
                     } else if ch == '-' {
                         4
-                    } else if ch == 'e' {
-                        6
                     } else if ch == '|' {
-                        9
+                        return self.token(VBAR, start_tc, next_tc)
+                    } else if ch == '?' {
+                        return self.token(QUESTION, start_tc, next_tc)
+                    } else if ch == '*' {
+                        return self.token(STAR, start_tc, next_tc)
+                    } else if ch == '+' {
+                        return self.token(PLUS, start_tc, next_tc)
+                    } else if ch == '(' {
+                        return self.token(LPAREN, start_tc, next_tc)
+                    } else if ch == ')' {
+                        return self.token(RPAREN, start_tc, next_tc)
+                    } else if ch == '{' {
+                        depth = 1;
+                        11
+                    } else if ch == '%' {
+                        13
+                    } else if ch == '"' {
+                        20
+                    } else if ch == '#' {
+                        15
+                    } else if ch == '/' {
+                        16
                     } else if Lexer::big(ch) {
                         7
                     } else {
-                        self.failed = true;
-                        return Some(Err(UnexpectedCharacter(ch)))
+                        let pos = self.position(tc);
+                        return self.error(UnexpectedCharacter(ch, pos))
                     }
                 } 2 => {
                     if Lexer::white(ch) {
@@ -101,57 +292,279 @@ impl<'a> Iterator<Result<Token<'a>,LexError>> for Lexer<'a> {
                         start_tc = tc;
                         1
                     }
-                } 3 => {
-                    self.tc = next_tc;
-                    return Some(Ok(Token{token:SEMI,lexeme:text.slice(start_tc,tc)}))
                 } 4 => {
                     if ch == '>' {
-                        5
+                        return self.token(ARROW, start_tc, next_tc)
                     } else {
-                        return Some(Err(UnexpectedCharacter(ch)))
+                        let pos = self.position(tc);
+                        return self.error(UnexpectedCharacter(ch, pos))
                     }
-                } 5 => {
-                    self.tc = next_tc;
-                    return Some(Ok(Token{token:ARROW,lexeme:text.slice(start_tc,tc)}))
-                } 6 => {
-                    self.tc = next_tc;
-                    return Some(Ok(Token{token:EMPTY,lexeme:text.slice(start_tc,tc)}))
                 } 7 => {
                     if Lexer::big(ch) {
                         10
                     } else if Lexer::not_big(ch) {
                         8
                     } else {
-                        self.tc = next_tc;
-                        return Some(Ok(Token{token:NONTERM,lexeme:text.slice(start_tc,tc)}))
+                        return self.token(NONTERM, start_tc, tc)
                     }
                 } 8 => {
                     if Lexer::alpha_num(ch) {
                         8
                     } else {
-                        self.tc = next_tc;
-                        return Some(Ok(Token{token:NONTERM,lexeme:text.slice(start_tc,tc)}))
+                        return self.token(NONTERM, start_tc, tc)
                     }
-                } 9 => {
-                    self.tc = next_tc;
-                    return Some(Ok(Token{token:VBAR,lexeme:text.slice(start_tc,tc)}))
                 } 10 => {
                     if Lexer::big(ch) {
                         10
                     } else if Lexer::not_big(ch) {
                         8
                     } else {
-                        self.tc = next_tc;
-                        return Some(Ok(Token{token:TERM,lexeme:text.slice(start_tc,tc)}))
+                        return self.token(TERM, start_tc, tc)
+                    }
+                } 11 => {
+                    // an action: everything up to the matching close brace,
+                    // not counting braces in string and character literals
+                    if ch == '{' {
+                        depth += 1;
+                    } else if ch == '}' {
+                        depth -= 1;
+                    }
+                    if depth == 0 {
+                        return self.token(ACTION, start_tc, next_tc)
+                    } else if ch == '"' {
+                        22
+                    } else if ch == '\'' {
+                        24
+                    } else {
+                        11
                     }
+                } 22 => {
+                    // a string literal in an action
+                    if ch == '"' {
+                        11
+                    } else if ch == '\\' {
+                        23
+                    } else {
+                        22
+                    }
+                } 23 => {
+                    22
+                } 24 => {
+                    // a character literal or a lifetime in an action
+                    if ch == '\\' {
+                        27
+                    } else {
+                        25
+                    }
+                } 25 => {
+                    if ch == '\'' {
+                        11
+                    } else {
+                        // a lifetime, `'a`: the character is part of the code
+                        next_tc = tc;
+                        11
+                    }
+                } 27 => {
+                    // the escaped character, which may be a quote
+                    26
+                } 26 => {
+                    // the rest of an escape, `'\n'` or `'\u{7d}'`, up to the
+                    // closing quote
+                    if ch == '\'' {
+                        11
+                    } else {
+                        26
+                    }
+                } 13 => {
+                    if Lexer::small(ch) {
+                        14
+                    } else {
+                        let pos = self.position(tc);
+                        return self.error(UnexpectedCharacter(ch, pos))
+                    }
+                } 14 => {
+                    // a directive such as %left
+                    if Lexer::small(ch) {
+                        14
+                    } else {
+                        return self.directive_token(start_tc, tc)
+                    }
+                } 15 => {
+                    // a line comment
+                    if ch == '\n' {
+                        2
+                    } else {
+                        15
+                    }
+                } 16 => {
+                    if ch == '/' {
+                        15
+                    } else if ch == '*' {
+                        depth = 1;
+                        17
+                    } else {
+                        let pos = self.position(tc);
+                        return self.error(UnexpectedCharacter(ch, pos))
+                    }
+                } 17 => {
+                    // a block comment, nested `depth` deep
+                    if ch == '*' {
+                        18
+                    } else if ch == '/' {
+                        19
+                    } else {
+                        17
+                    }
+                } 18 => {
+                    if ch == '/' {
+                        depth -= 1;
+                        if depth == 0 {
+                            2
+                        } else {
+                            17
+                        }
+                    } else if ch == '*' {
+                        18
+                    } else {
+                        17
+                    }
+                } 19 => {
+                    if ch == '*' {
+                        depth += 1;
+                        17
+                    } else if ch == '/' {
+                        19
+                    } else {
+                        17
+                    }
+                } 20 => {
+                    // a quoted literal, backslash escapes the next character
+                    if ch == '"' {
+                        return self.token(LITERAL, start_tc, next_tc)
+                    } else if ch == '\\' {
+                        21
+                    } else {
+                        20
+                    }
+                } 21 => {
+                    20
                 } _ => {
-                    self.failed = true;
-                    return Some(Err(BadState(state)))
+                    let pos = self.position(tc);
+                    return self.error(BadState(state, pos))
                 }
             };
             tc = next_tc;
         }
-        None
+        // the end of the input ends the token in progress
+        match state {
+            7 | 8 => { self.token(NONTERM, start_tc, tc) }
+            10 => { self.token(TERM, start_tc, tc) }
+            14 => { self.directive_token(start_tc, tc) }
+            4 | 13 | 16 => {
+                let ch = text.char_at(start_tc);
+                let pos = self.position(start_tc);
+                self.error(UnexpectedCharacter(ch, pos))
+            }
+            11 | 22 | 23 | 24 | 25 | 26 | 27 => {
+                let pos = self.position(start_tc);
+                self.error(UnterminatedAction(pos))
+            }
+            17 | 18 | 19 => {
+                let pos = self.position(start_tc);
+                self.error(UnterminatedComment(pos))
+            }
+            20 | 21 => {
+                let pos = self.position(start_tc);
+                self.error(UnterminatedLiteral(pos))
+            }
+            _ => {
+                self.advance(tc);
+                None
+            }
+        }
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::{gram_lexer, Token, LexError, Position, TokenType, TERM, NONTERM, LITERAL};
+    use super::{UnterminatedComment, UnterminatedAction, ACTION};
+
+    fn lex<'a>(text : &'a str) -> Result<Vec<Token<'a>>, LexError> {
+        let mut tokens : Vec<Token<'a>> = Vec::new();
+        for result in gram_lexer(text) {
+            match result {
+                Ok(token) => { tokens.push(token); }
+                Err(err) => { return Err(err) }
+            }
+        }
+        Ok(tokens)
+    }
+
+    fn kinds(text : &str) -> Vec<(TokenType, String)> {
+        match lex(text) {
+            Ok(tokens) => { tokens.iter().map(|t| (t.token.clone(), t.lexeme.to_string())).collect() }
+            Err(err) => { fail!("{}", err.message()) }
+        }
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(kinds("Aa /* x /* y */ z */ BB"), vec![(NONTERM, "Aa".to_string()), (TERM, "BB".to_string())]);
+        assert_eq!(kinds("Aa /**/ /* /**/ */BB"), vec![(NONTERM, "Aa".to_string()), (TERM, "BB".to_string())]);
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        match lex("Aa /* /* */") {
+            Err(UnterminatedComment(pos)) => {
+                assert_eq!((pos.offset, pos.line, pos.column), (3, 1, 4));
+            }
+            _ => { fail!("the unterminated comment was not reported") }
+        }
+    }
+
+    #[test]
+    fn line_comments_at_the_end_of_the_input() {
+        assert_eq!(kinds("Aa # a comment"), vec![(NONTERM, "Aa".to_string())]);
+        assert_eq!(kinds("Aa // a comment"), vec![(NONTERM, "Aa".to_string())]);
+        assert_eq!(kinds("Aa #"), vec![(NONTERM, "Aa".to_string())]);
+        assert_eq!(kinds("Aa //"), vec![(NONTERM, "Aa".to_string())]);
+    }
+
+    fn positions(text : &str) -> Vec<Position> {
+        match lex(text) {
+            Ok(tokens) => { tokens.iter().map(|t| t.pos.clone()).collect() }
+            Err(err) => { fail!("{}", err.message()) }
+        }
+    }
+
+    fn at(offset : uint, line : uint, column : uint) -> Position {
+        Position{offset: offset, line: line, column: column}
+    }
+
+    #[test]
+    fn columns_count_characters_and_offsets_bytes() {
+        assert_eq!(kinds("\"\u00e9\" Bb"), vec![(LITERAL, "\"\u00e9\"".to_string()), (NONTERM, "Bb".to_string())]);
+        assert_eq!(positions("\"\u00e9\" Bb"), vec![at(0, 1, 1), at(5, 1, 5)]);
+        assert_eq!(positions("/* \u00fc\u00fc */ Aa"), vec![at(11, 1, 10)]);
+    }
+
+    #[test]
+    fn crlf_ends_a_line() {
+        assert_eq!(positions("Aa\r\nBb"), vec![at(0, 1, 1), at(4, 2, 1)]);
+        assert_eq!(positions("# \u00fc\r\n  Cc ;"), vec![at(8, 2, 3), at(11, 2, 6)]);
+    }
+
+    #[test]
+    fn actions_skip_braces_in_literals() {
+        let code = "{ s.push('}'); let t = \"{\"; }";
+        assert_eq!(kinds(code), vec![(ACTION, code.to_string())]);
+        let code = "{ fn f<'a>(x : &'a str) -> char { '\\'' } }";
+        assert_eq!(kinds(code), vec![(ACTION, code.to_string())]);
+        match lex("{ \"}\" ") {
+            Err(UnterminatedAction(pos)) => { assert_eq!(pos.offset, 0); }
+            _ => { fail!("the unterminated action was not reported") }
+        }
+    }
+}
@@ -7,38 +7,105 @@
 extern crate collections;
 
 use self::collections::Vec;
+use std::cell::RefCell;
 use gram_lexer::*;
 
 #[deriving(Show)]
 pub struct Node {
     pub label : String,
-    pub kids : Vec<Box<Node>>
+    pub kids : Vec<Box<Node>>,
+    pub pos : Option<Position>
 }
 
-#[deriving(Show)]
-enum ParseError {
+#[deriving(Show, Clone)]
+pub enum ParseError {
     Lex(LexError),
     NotImplemented,
-    NoMoreInputExpected(TokenType),
-    ExpectedButGot(TokenType,TokenType),
-    UnconsumedInput(String)
+    NoMoreInputExpected(TokenType, Position),
+    ExpectedButGot(TokenType, TokenType, String, Position),
+    UnconsumedInput(String, Position)
+}
+
+impl ParseError {
+    pub fn position(&self) -> Option<Position> {
+        match *self {
+            Lex(ref err) => { Some(err.position()) }
+            NotImplemented => { None }
+            NoMoreInputExpected(_, ref pos) => { Some(pos.clone()) }
+            ExpectedButGot(_, _, _, ref pos) => { Some(pos.clone()) }
+            UnconsumedInput(_, ref pos) => { Some(pos.clone()) }
+        }
+    }
+
+    /// The length of the text the error is about, for underlining.
+    pub fn len(&self) -> uint {
+        match *self {
+            Lex(ref err) => { err.len() }
+            ExpectedButGot(_, _, ref lexeme, _) => { lexeme.as_slice().char_len() }
+            UnconsumedInput(ref lexeme, _) => { lexeme.as_slice().char_len() }
+            _ => { 1 }
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            Lex(ref err) => { err.message() }
+            NotImplemented => { "not implemented".to_string() }
+            NoMoreInputExpected(ref expected, _) => {
+                format!("expected {} but reached the end of the grammar", expected.describe())
+            }
+            ExpectedButGot(ref expected, _, ref lexeme, _) => {
+                format!("expected {} but got '{}'", expected.describe(), lexeme)
+            }
+            UnconsumedInput(ref lexeme, _) => { format!("unexpected '{}'", lexeme) }
+        }
+    }
 }
 
 impl Node {
-    fn new(label : &str) -> Node {
+    pub fn new(label : &str) -> Node {
         return Node{
             label: label.to_string(),
-            kids: Vec::new()
+            kids: Vec::new(),
+            pos: None
         }
     }
-    fn addkid(mut self, node : Node) -> Node {
+    pub fn at(mut self, pos : Position) -> Node {
+        self.pos = Some(pos);
+        self
+    }
+    pub fn addkid(mut self, node : Node) -> Node {
         self.kids.push(box node);
         return self
     }
+    /// Puts a node at the front of the kids, the node then starts where its
+    /// new first kid does.
     fn enquekid(mut self, node : Node) -> Node {
+        if node.pos.is_some() {
+            self.pos = node.pos.clone();
+        }
         self.kids.insert(0, box node);
         self
     }
+
+    /// Renders the tree one node per line, with the kids of a node indented
+    /// under it.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.pretty_into(&mut out, 0);
+        out
+    }
+
+    fn pretty_into(&self, out : &mut String, depth : uint) {
+        for _ in range(0, depth) {
+            out.push_str("  ");
+        }
+        out.push_str(self.label.as_slice());
+        out.push_str("\n");
+        for kid in self.kids.iter() {
+            kid.pretty_into(out, depth + 1);
+        }
+    }
 }
 
 pub fn parse<'a, 'b>(lexer : &mut Lexer<'a>) -> Result<Node,ParseError> {
@@ -49,14 +116,38 @@ pub fn parse<'a, 'b>(lexer : &mut Lexer<'a>) -> Result<Node,ParseError> {
     parser.parse()
 }
 
+/// The text of a quoted literal with its escapes, `\"`, `\\`, `\n` and `\t`,
+/// replaced. Any other escaped character stands for itself.
+fn unescape(quoted : &str) -> String {
+    let mut text = String::new();
+    let mut escaped = false;
+    for ch in quoted.chars() {
+        if escaped {
+            text.push(match ch {
+                'n' => { '\n' }
+                't' => { '\t' }
+                _ => { ch }
+            });
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else {
+            text.push(ch);
+        }
+    }
+    text
+}
+
 struct Parser<'a> {
-    tokens : Vec<Token<'a>>
+    tokens : Vec<Token<'a>>,
+    end : Position,
+    furthest : RefCell<Option<(uint, ParseError)>>
 }
 
 impl<'a> Parser<'a> {
     fn new<'a>(lexer : &mut Lexer<'a>) -> Result<Parser<'a>,LexError> {
         let mut tokens : Vec<Token<'a>> = Vec::new();
-        for res in lexer {
+        for res in lexer.by_ref() {
             match res {
                 Ok(tok) => {
                     tokens.push(tok)
@@ -66,13 +157,26 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        return Ok(Parser{ tokens : tokens })
+        return Ok(Parser{
+            tokens : tokens,
+            end : lexer.end(),
+            furthest : RefCell::new(None)
+        })
     }
 
+    /// Parses the whole grammar. When some input is left over the error is
+    /// the one from the parse which got the furthest into the input, as it
+    /// is the most likely to point at the actual mistake.
     fn parse(&self) -> Result<Node,ParseError> {
         let (i, node) = try!(self.Productions(0));
         if i != self.tokens.len() {
-            return Err(UnconsumedInput(format!("{}", self.tokens.slice(i, self.tokens.len()))))
+            let furthest = self.furthest.borrow().clone();
+            match furthest {
+                Some((j, ref err)) if j >= i => { return Err(err.clone()) }
+                _ => {}
+            }
+            let tok = self.tokens[i];
+            return Err(UnconsumedInput(tok.lexeme.to_string(), tok.pos))
         }
         return Ok(node)
     }
@@ -80,24 +184,101 @@ impl<'a> Parser<'a> {
     #[allow(non_snake_case)]
     fn Productions(&self, i : uint) -> Result<(uint,Node),ParseError> {
         self.epsilon(i, Node::new("Grammar"), |i| {
-            let (a, prod) = try!(self.Production(i));
+            let (a, prod) = try!(self.Declaration(i).or_else(|_| self.Production(i)));
             let (b, list) = try!(self.Productions(a));
             return Ok((b, list.enquekid(prod)))
         })
     }
 
+    #[allow(non_snake_case)]
+    fn Declaration(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        self.Start(i).or_else(|_| self.Token(i)).or_else(|_| self.Precedence(i))
+    }
+
+    #[allow(non_snake_case)]
+    fn Start(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, START));
+        let (k, nt) = try!(self.NonTerm(j));
+        let (l, nts) = try!(self.NonTerms(k));
+        return Ok((l, nts.enquekid(nt).at(tok.pos)));
+    }
+
+    #[allow(non_snake_case)]
+    fn NonTerms(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        self.epsilon(i, Node::new("Start"), |i| {
+            let (j, nt) = try!(self.NonTerm(i));
+            let (k, nts) = try!(self.NonTerms(j));
+            return Ok((k, nts.enquekid(nt)))
+        })
+    }
+
+    #[allow(non_snake_case)]
+    fn NonTerm(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        return self.consume(i, NONTERM).and_then(|(j, tok)| {
+            Ok((j, Node::new("NonTerm").at(tok.pos).addkid(Node::new(tok.lexeme).at(tok.pos))))
+        });
+    }
+
+    #[allow(non_snake_case)]
+    fn Token(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, TOKEN));
+        let (k, term) = try!(self.Terminal(j));
+        let (l, terms) = try!(self.Terms(k, "Token"));
+        return Ok((l, terms.enquekid(term).at(tok.pos)));
+    }
+
+    #[allow(non_snake_case)]
+    fn Precedence(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, assoc) = try!(self.consume(i, LEFT).and_then(|(j,tok)| {Ok((j, Node::new("left").at(tok.pos)))})
+            .or_else(|_| self.consume(i, RIGHT).and_then(|(j,tok)| {Ok((j, Node::new("right").at(tok.pos)))}))
+            .or_else(|_| self.consume(i, NONASSOC).and_then(|(j,tok)| {Ok((j, Node::new("nonassoc").at(tok.pos)))})));
+        let (k, term) = try!(self.Terminal(j));
+        let (l, terms) = try!(self.Terms(k, "Precedence"));
+        return Ok((l, terms.enquekid(term).enquekid(assoc)));
+    }
+
+    #[allow(non_snake_case)]
+    fn Terms(&self, i : uint, label : &str) -> Result<(uint,Node),ParseError> {
+        self.epsilon(i, Node::new(label), |i| {
+            let (j, term) = try!(self.Terminal(i));
+            let (k, terms) = try!(self.Terms(j, label));
+            return Ok((k, terms.enquekid(term)))
+        })
+    }
+
+    #[allow(non_snake_case)]
+    fn Term(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        return self.consume(i, TERM).and_then(|(j, tok)| {
+            Ok((j, Node::new("Term").at(tok.pos).addkid(Node::new(tok.lexeme).at(tok.pos))))
+        });
+    }
+
+    #[allow(non_snake_case)]
+    fn Literal(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        return self.consume(i, LITERAL).and_then(|(j, tok)| {
+            let text = unescape(tok.lexeme.slice(1, tok.lexeme.len() - 1));
+            Ok((j, Node::new("Literal").at(tok.pos).addkid(Node::new(text.as_slice()).at(tok.pos))))
+        });
+    }
+
+    #[allow(non_snake_case)]
+    fn Terminal(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        return self.Term(i).or(self.Literal(i));
+    }
+
     #[allow(non_snake_case)]
     fn Production(&self, i : uint) -> Result<(uint,Node),ParseError> {
-        let (a, nt) = try!(self.consume(i, NONTERM).and_then(|(a,tok)| {Ok((a,Node::new("NonTerm").addkid(Node::new(tok.lexeme))))}));
+        let (a, nt) = try!(self.NonTerm(i));
         let (b, _) = try!(self.consume(a, ARROW));
         let (c, body) = try!(self.Body(b));
         let (d, _) = try!(self.consume(c, SEMI));
-        return Ok((d, Node::new("Production").addkid(nt).addkid(body)));
+        let pos = nt.pos.clone().unwrap();
+        return Ok((d, Node::new("Production").at(pos).addkid(nt).addkid(body)));
     }
 
     #[allow(non_snake_case)]
     fn Body(&self, i : uint) -> Result<(uint,Node),ParseError> {
-        let (j, rule) = try!(self.Rule(i));
+        let (j, rule) = try!(self.Alternative(i));
         let (k, body) = try!(self.Body_(j));
         return Ok((k, body.enquekid(rule)));
     }
@@ -106,30 +287,82 @@ impl<'a> Parser<'a> {
     fn Body_(&self, i : uint) -> Result<(uint,Node),ParseError> {
         self.epsilon(i, Node::new("Body"), |i| {
             let (j, _) = try!(self.consume(i, VBAR));
-            let (k, rule) = try!(self.Rule(j));
+            let (k, rule) = try!(self.Alternative(j));
             let (l, body) = try!(self.Body_(k));
             return Ok((l, body.enquekid(rule)))
         })
     }
 
+    /// `%empty` may only start an alternative, so it is tried here rather
+    /// than in `Rule`.
+    #[allow(non_snake_case)]
+    fn Alternative(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        self.Empty(i).or_else(|_| self.Rule(i))
+    }
+
     #[allow(non_snake_case)]
     fn Rule(&self, i : uint) -> Result<(uint,Node),ParseError> {
         self.epsilon(i, Node::new("Rule"), |i| {
-            let (j, sym) = try!(self.Symbol(i));
-            let (k, rule) = try!(self.Rule(j));
-            return Ok((k, rule.enquekid(sym)))
+            self.Action(i).or_else(|_| self.Prec(i)).or_else(|_| {
+                let (j, item) = try!(self.Item(i));
+                let (k, rule) = try!(self.Rule(j));
+                return Ok((k, rule.enquekid(item)))
+            })
         })
     }
 
+    /// A symbol or a parenthesized group of alternatives, optionally
+    /// followed by `?`, `*` or `+`.
+    #[allow(non_snake_case)]
+    fn Item(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, primary) = try!(self.Symbol(i).or_else(|_| self.Group(i)));
+        let pos = primary.pos.clone().unwrap();
+        let suffix = self.consume(j, QUESTION).map(|(k,_)| (k, "Optional"))
+            .or_else(|_| self.consume(j, STAR).map(|(k,_)| (k, "Repeat")))
+            .or_else(|_| self.consume(j, PLUS).map(|(k,_)| (k, "Repeat1")));
+        return match suffix {
+            Ok((k, label)) => { Ok((k, Node::new(label).at(pos).addkid(primary))) }
+            Err(_) => { Ok((j, primary)) }
+        };
+    }
+
+    #[allow(non_snake_case)]
+    fn Group(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, LPAREN));
+        let (k, body) = try!(self.Body(j));
+        let (l, _) = try!(self.consume(k, RPAREN));
+        let mut group = Node::new("Group").at(tok.pos);
+        group.kids = body.kids;
+        return Ok((l, group));
+    }
+
+    #[allow(non_snake_case)]
+    fn Prec(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, PREC));
+        let (k, term) = try!(self.Terminal(j));
+        let (l, rule) = self.Action(k).unwrap_or((k, Node::new("Rule")));
+        return Ok((l, rule.enquekid(Node::new("Prec").at(tok.pos).addkid(term))));
+    }
+
+    /// `%empty`, marking an alternative that derives the empty string. Only
+    /// a `%prec` or an action may follow it.
+    #[allow(non_snake_case)]
+    fn Empty(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, EMPTY));
+        let (k, rule) = self.Prec(j).or_else(|_| self.Action(j)).unwrap_or((j, Node::new("Rule")));
+        return Ok((k, rule.enquekid(Node::new("Empty").at(tok.pos))));
+    }
+
+    #[allow(non_snake_case)]
+    fn Action(&self, i : uint) -> Result<(uint,Node),ParseError> {
+        let (j, tok) = try!(self.consume(i, ACTION));
+        let action = Node::new("Action").at(tok.pos).addkid(Node::new(tok.lexeme.slice(1, tok.lexeme.len() - 1)).at(tok.pos));
+        return Ok((j, Node::new("Rule").at(tok.pos).addkid(action)));
+    }
+
     #[allow(non_snake_case)]
     fn Symbol(&self, i : uint) -> Result<(uint,Node),ParseError> {
-        return self.consume(i, TERM).and_then(|(j, tok)| {
-                    Ok((j, Node::new("Term").addkid(Node::new(tok.lexeme))))
-                }).or(self.consume(i, NONTERM).and_then(|(j, tok)| {
-                    Ok((j, Node::new("NonTerm").addkid(Node::new(tok.lexeme))))
-                }).or(self.consume(i, EMPTY).and_then(|(j, tok)| {
-                    Ok((j, Node::new("Empty").addkid(Node::new(tok.lexeme))))
-                })));
+        return self.Terminal(i).or(self.NonTerm(i));
     }
 
     fn epsilon<T, E>(&self, i : uint, n : T, f : |uint| -> Result<(uint,T),E>) -> Result<(uint,T),E> {
@@ -138,14 +371,26 @@ impl<'a> Parser<'a> {
 
     fn consume<'a>(&'a self, i : uint, toktype : TokenType) -> Result<(uint, Token<'a>), ParseError> {
         if i >= self.tokens.len() {
-            return Err(NoMoreInputExpected(toktype));
+            return Err(self.fail(i, NoMoreInputExpected(toktype, self.end.clone())));
         }
         let tok = self.tokens[i];
         return if tok.token == toktype {
                    Ok((i+1, tok))
                } else {
-                   Err(ExpectedButGot(toktype, tok.token))
+                   Err(self.fail(i, ExpectedButGot(toktype, tok.token, tok.lexeme.to_string(), tok.pos)))
                };
     }
+
+    /// Remembers the error if it is the furthest into the input so far.
+    fn fail(&self, i : uint, err : ParseError) -> ParseError {
+        let further = match *self.furthest.borrow() {
+            Some((j, _)) => { i >= j }
+            None => { true }
+        };
+        if further {
+            *self.furthest.borrow_mut() = Some((i, err.clone()));
+        }
+        err
+    }
 }
 
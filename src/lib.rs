@@ -0,0 +1,94 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! Reads grammars, analyzes them and builds LR parse tables.
+//!
+//!     let grammar = match slr::Grammar::from_str(text) {
+//!         Ok(grammar) => { grammar }
+//!         Err(err) => { fail!("{}", err) }
+//!     };
+//!     let automaton = grammar.lr0_automaton();
+//!     let table = automaton.lalr_table();
+//!
+//! The modules are public for tools that need more than the types exported
+//! here, such as the grammar's syntax tree.
+
+#![feature(globs)]
+#![feature(macro_rules)]
+
+use std::fmt::{Formatter,Show,FormatError};
+
+pub use gram_lexer::Position;
+pub use gram_parser::{Node, ParseError};
+pub use slr::{Symbol, Term, NonTerm, EmptyString, EndOfInput};
+pub use slr::{Grammar, GrammarBuilder, nt, t, GrammarAnalysis, Production, Assoc, Item, LR1Item, SortedSet};
+pub use slr::{Diagnostic, NoStartSymbol, UndefinedNonTerm, UnreachableNonTerm, UnproductiveNonTerm, UnusedTerm, MisplacedSymbol};
+pub use slr::{Automaton, SLRAutomaton, LR1Automaton};
+pub use slr::{LRTable, SLRTable, LRAction, Conflict, Resolution};
+pub use slr::{Parser, InputToken, ParseTree, SyntaxError};
+
+pub mod gram_lexer;
+pub mod gram_parser;
+pub mod slr;
+pub mod diagnostic;
+pub mod tables;
+pub mod gen_rust;
+pub mod gen_c;
+pub mod gen_dot;
+pub mod build;
+
+/// Why a grammar could not be loaded.
+#[deriving(Clone)]
+pub enum Error {
+    /// The text is not a well formed grammar.
+    Parse(ParseError),
+    /// The grammar was read but `Grammar::validate` found errors in it. All
+    /// of its diagnostics are kept, warnings included.
+    Invalid(Vec<Diagnostic>)
+}
+
+impl Error {
+    /// Formats every problem as `path:line:col: message` with the offending
+    /// line of `text` quoted below it, see `diagnostic::format`.
+    pub fn format(&self, path : &str, text : &str) -> String {
+        let mut out = String::new();
+        match *self {
+            Parse(ref err) => {
+                out.push_str(describe(path, text, err.position(), err.len(), err.message()).as_slice());
+            }
+            Invalid(ref diagnostics) => {
+                for d in diagnostics.iter() {
+                    if out.len() > 0 {
+                        out.push_str("\n");
+                    }
+                    out.push_str(describe(path, text, d.position(), d.len(), d.message()).as_slice());
+                }
+            }
+        }
+        out
+    }
+}
+
+fn describe(path : &str, text : &str, pos : Option<Position>, len : uint, message : String) -> String {
+    match pos {
+        Some(pos) => { diagnostic::format(path, text, &pos, len, message.as_slice()) }
+        None => { format!("{}: {}", path, message) }
+    }
+}
+
+impl Show for Error {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        match *self {
+            Parse(ref err) => {
+                fmtr.write_str(err.message().as_slice()).ok();
+            }
+            Invalid(ref diagnostics) => {
+                let messages : Vec<String> = diagnostics.iter().map(|d| d.message()).collect();
+                fmtr.write_str(messages.connect("\n").as_slice()).ok();
+            }
+        }
+        return Ok(());
+    }
+}
@@ -8,17 +8,14 @@
 
 extern crate getopts;
 extern crate libc;
+extern crate slr;
 
 use std::os;
 use std::io;
 use std::result::Result;
 
-use gram_lexer::gram_lexer;
-use gram_parser::parse;
-
-mod gram_parser;
-mod gram_lexer;
-mod slr;
+use slr::{Automaton, Grammar};
+use slr::diagnostic;
 
 macro_rules! log(($fmt:expr$(, $msg:expr)*) => {
     (writeln![io::stderr(), $fmt $(, $msg)*]).ok().expect("log failed")
@@ -46,6 +43,10 @@ impl<'a> MainConfig<'a> {
         }
     }
 
+    fn write_file_or_die(&self, path : &Path, text : &str) {
+        self.unwrap_or_die(io::File::create(path).write_str(text))
+    }
+
     fn read_file_or_die(&self, path : &str) -> String {
         self.unwrap_or_die(io::File::open(&Path::new(path))
           .read_to_end()
@@ -58,6 +59,11 @@ impl<'a> MainConfig<'a> {
 fn main() {
     let cfg : MainConfig = MainConfig{options: &[
         getopts::optopt("g", "grammar", "the grammar to read", "<path>"),
+        getopts::optopt("a", "algorithm", "the table construction to use: slr (default), lalr, lr1 or pager (minimal LR(1))", "<slr|lalr|lr1|pager>"),
+        getopts::optopt("t", "tokens", "a file of tokens to parse, one `TERMINAL lexeme` per line", "<path>"),
+        getopts::optopt("e", "emit", "write a parser for the grammar, or a Graphviz drawing of its LR(0) automaton, instead of printing its tables", "<rust|c|dot>"),
+        getopts::optopt("o", "output", "where --emit writes its output (default: standard output); for c, the path of the .c file, the .h file is written next to it", "<path>"),
+        getopts::optopt("s", "start", "the start symbol to parse the tokens from (default: the first)", "<Nonterm>"),
         getopts::optflag("h", "help", "print this help menu")
     ]};
 
@@ -75,15 +81,181 @@ fn main() {
         None => { cfg.usage(); return }
     };
 
+    let algorithm = opts.opt_str("a").unwrap_or("slr".to_string());
+    let algorithm_name = match algorithm.as_slice() {
+        "slr" => { "SLR(1)" }
+        "lalr" => { "LALR(1)" }
+        "lr1" => { "LR(1)" }
+        "pager" => { "minimal LR(1)" }
+        _ => {
+            log!("unknown algorithm {}", algorithm);
+            cfg.usage();
+            return
+        }
+    };
+
+    let emit = opts.opt_str("e");
+    match emit {
+        Some(ref lang) if lang.as_slice() != "rust" && lang.as_slice() != "c" && lang.as_slice() != "dot" => {
+            log!("unknown language {}", lang);
+            cfg.usage();
+            return
+        }
+        Some(ref lang) if lang.as_slice() == "c" && !opts.opt_present("o") => {
+            log!("--emit c needs --output");
+            cfg.usage();
+            return
+        }
+        Some(ref lang) if lang.as_slice() == "dot" && algorithm.as_slice() != "slr" && algorithm.as_slice() != "lalr" => {
+            log!("--emit dot draws the LR(0) automaton, use it with -a slr or -a lalr");
+            cfg.usage();
+            return
+        }
+        _ => {}
+    }
+
     log!("grammar path = {}", grammar_path);
 
-    let grammar : String = cfg.read_file_or_die(grammar_path.as_slice());
+    let text : String = cfg.read_file_or_die(grammar_path.as_slice());
+
+    log!("the grammar from {} is {} characters long", grammar_path, text.len());
+    let grammar = match Grammar::from_str(text.as_slice()) {
+        Ok(grammar) => { grammar }
+        Err(err) => {
+            log!("{}", err.format(grammar_path.as_slice(), text.as_slice()));
+            os::set_exit_status(1);
+            return
+        }
+    };
+    for d in grammar.validate().iter() {
+        match d.position() {
+            Some(pos) => {
+                log!("{}", diagnostic::format(grammar_path.as_slice(), text.as_slice(), &pos, d.len(), d.message().as_slice()));
+            }
+            None => {
+                log!("{}: {}", grammar_path, d.message());
+            }
+        }
+    }
+    for (literal, name) in grammar.literals().iter() {
+        log!("literal \"{}\" is terminal {}", literal.as_slice().escape_default(), name);
+    }
+    let automaton = grammar.lr0_automaton();
+    let canonical = match algorithm.as_slice() {
+        "lr1" => { Some(grammar.lr1_automaton()) }
+        "pager" => { Some(grammar.minimal_lr1_automaton()) }
+        _ => { None }
+    };
+    if emit.is_none() {
+        match canonical {
+            Some(ref lr1) => { println!("{}", lr1); }
+            None => { println!("{}", automaton); }
+        }
+        println!("\n");
+    }
+    let table = match canonical {
+        Some(ref lr1) => { lr1.table() }
+        None => {
+            match algorithm.as_slice() {
+                "lalr" => { automaton.lalr_table() }
+                _ => { automaton.table() }
+            }
+        }
+    };
+    match emit {
+        Some(ref lang) if lang.as_slice() != "dot" && table.has_conflicts() => {
+            // the conflicts are reported below
+            log!("not writing the {} parser: the table has conflicts", lang);
+        }
+        Some(ref lang) if lang.as_slice() == "c" => {
+            let tables = cfg.unwrap_or_die(slr::tables::Tables::new(&grammar, &table));
+            let path = Path::new(opts.opt_str("o").unwrap());
+            let name = path.filestem_str().unwrap_or("parser").to_string();
+            let (header, source) = slr::gen_c::generate(&tables, grammar_path.as_slice(), name.as_slice());
+            cfg.write_file_or_die(&path.with_extension("h"), header.as_slice());
+            cfg.write_file_or_die(&path.with_extension("c"), source.as_slice());
+        }
+        Some(ref lang) if lang.as_slice() == "dot" => {
+            let graph = slr::gen_dot::generate(&automaton, &table, grammar_path.as_slice());
+            match opts.opt_str("o") {
+                Some(path) => { cfg.write_file_or_die(&Path::new(path), graph.as_slice()); }
+                None => { print!("{}", graph); }
+            }
+        }
+        Some(_) => {
+            let tables = cfg.unwrap_or_die(slr::tables::Tables::new(&grammar, &table));
+            let code = slr::gen_rust::generate(&tables, grammar_path.as_slice());
+            match opts.opt_str("o") {
+                Some(path) => { cfg.write_file_or_die(&Path::new(path), code.as_slice()); }
+                None => { print!("{}", code); }
+            }
+        }
+        None => { println!("{}", table); }
+    }
+    for resolution in table.resolutions().iter() {
+        log!("{}", resolution);
+    }
+    if table.has_conflicts() {
+        for conflict in table.conflicts().iter() {
+            log!("{}", diagnostic::conflict(grammar_path.as_slice(), text.as_slice(), conflict));
+        }
+        log!("the grammar is not {}: {} conflicts", algorithm_name, table.conflicts().len());
+        if canonical.is_none() {
+            let lr1 = grammar.lr1_automaton();
+            if lr1.table().has_conflicts() {
+                log!("the conflicts are inherent to the grammar: it is not LR(1) either");
+            } else {
+                log!("the grammar is LR(1): the conflicts come from the {} construction", algorithm_name);
+            }
+        }
+        os::set_exit_status(1);
+    }
+
+    match opts.opt_str("t") {
+        Some(tokens_path) => {
+            let text = cfg.read_file_or_die(tokens_path.as_slice());
+            let parser = match opts.opt_str("s") {
+                Some(start) => {
+                    match slr::Parser::with_start(&table, start.as_slice()) {
+                        Some(parser) => { parser }
+                        None => {
+                            log!("{} is not a start symbol of {}", start, grammar_path);
+                            os::set_exit_status(1);
+                            return
+                        }
+                    }
+                }
+                None => { slr::Parser::new(&table) }
+            };
+            match parser.parse(read_tokens(text.as_slice()).into_iter()) {
+                Ok(tree) => { println!("{}", tree.to_node().pretty()); }
+                Err(err) => {
+                    log!("{}: {}", tokens_path, err);
+                    os::set_exit_status(1);
+                }
+            }
+        }
+        None => {}
+    }
+}
 
-    log!("the grammar from {} is {} characters long", grammar_path, grammar.len());
-    let grammar = cfg.unwrap_or_die(parse(&mut gram_lexer(grammar.as_slice())));
-    let grammar = slr::Grammar::new(grammar);
-    let automaton = grammar.LR0_automaton();
-    println!("{}", automaton);
-    println!("\n");
-    println!("{}", automaton.table());
+/// Reads a token file: each non-blank line holds a terminal name, optionally
+/// followed by its lexeme. The lexeme defaults to the terminal's name.
+fn read_tokens(text : &str) -> Vec<slr::InputToken> {
+    let mut tokens : Vec<slr::InputToken> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.len() == 0 {
+            continue
+        }
+        let (terminal, lexeme) = match line.find(|c : char| c.is_whitespace()) {
+            Some(i) => { (line.slice_to(i), line.slice_from(i).trim()) }
+            None => { (line, line) }
+        };
+        tokens.push(slr::InputToken{
+            terminal: slr::Term(terminal.to_string()),
+            lexeme: lexeme.to_string()
+        });
+    }
+    tokens
 }
@@ -6,14 +6,16 @@
 extern crate collections;
 
 use self::collections::Vec;
-use std::collections::{HashMap,HashSet,TreeMap};
+use std::collections::{HashMap,HashSet,TreeMap,TreeSet};
 use std::slice;
 use std::vec;
 use std::cmp;
 use std::fmt::{Formatter,Show,FormatError};
 use std::cell::RefCell;
+use std::rc::Rc;
 
-use gram_parser::Node;
+use gram_lexer::{Position, gram_lexer};
+use gram_parser::{Node, parse};
 
 
 fn find<T: Ord + Eq, S: Index<uint, T> + Collection>(items : &S, item : &T) -> (uint,bool) {
@@ -43,7 +45,8 @@ fn find<T: Ord + Eq, S: Index<uint, T> + Collection>(items : &S, item : &T) -> (
 pub enum Symbol {
     Term(String),
     NonTerm(String),
-    EmptyString
+    EmptyString,
+    EndOfInput
 }
 
 #[deriving(Show, Eq)]
@@ -106,6 +109,10 @@ impl<T: Ord + Clone> SortedSet<T> {
         let (_, has) = find(&self.items, item);
         return has;
     }
+
+    pub fn intersects(&self, other : &SortedSet<T>) -> bool {
+        self.iter().any(|item| other.contains(item))
+    }
 }
 
 impl<T: Ord + Clone> Collection for SortedSet<T> {
@@ -169,13 +176,57 @@ impl<T: Ord + Clone> Clone for SortedSet<T> {
     }
 }
 
-#[deriving(Hash, Clone, PartialEq, Ord, PartialOrd, Eq, Show)]
+#[deriving(Hash, Clone, PartialEq, Ord, PartialOrd, Eq)]
 pub struct Production {
     nt : String,
-    symbols : Vec<Symbol>
+    symbols : Vec<Symbol>,
+    action : Option<String>,
+    prec : Option<String>,
+    pos : Option<Position>
+}
+
+impl Show for Production {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        fmtr.write_str(format!("{} ->", self.nt).as_slice()).ok();
+        if self.symbols.len() == 0 {
+            fmtr.write_str(" %empty").ok();
+        }
+        for sym in self.symbols.iter() {
+            fmtr.write_str(format!(" {}", Grammar::name(sym.clone())).as_slice()).ok();
+        }
+        return Ok(());
+    }
+}
+
+/// The associativity of the terminals of a precedence declaration.
+#[deriving(Show, Clone, PartialEq)]
+pub enum Assoc {
+    Left,
+    Right,
+    NonAssoc
 }
 
 impl Production {
+    pub fn nt(&self) -> &String {
+        &self.nt
+    }
+
+    pub fn symbols(&self) -> &Vec<Symbol> {
+        &self.symbols
+    }
+
+    /// Where the production's alternative starts in the grammar text, if it
+    /// came from one.
+    pub fn pos(&self) -> Option<&Position> {
+        self.pos.as_ref()
+    }
+
+    /// The code of the action block written after the production's body, if
+    /// any, without the enclosing braces.
+    pub fn action(&self) -> Option<&String> {
+        self.action.as_ref()
+    }
+
     pub fn index_of(&self, symbol : &Symbol) -> Option<uint> {
         for (i, sym) in self.symbols.iter().enumerate() {
             if sym == symbol {
@@ -192,6 +243,16 @@ pub struct Item<'a> {
     dot : uint
 }
 
+impl<'a> Item<'a> {
+    pub fn production(&self) -> &'a Production {
+        self.production
+    }
+
+    pub fn dot(&self) -> uint {
+        self.dot
+    }
+}
+
 impl<'a> Clone for Item<'a> {
     fn clone(&self) -> Item<'a> {
         return Item{
@@ -253,34 +314,657 @@ impl<'a> Ord for Item<'a> {
     }
 }
 
+/// An LR(1) item: an LR(0) item together with the terminal which may follow
+/// the production once it has been reduced.
+#[deriving(Hash, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LR1Item<'a> {
+    item : Item<'a>,
+    lookahead : Symbol
+}
+
+impl<'a> LR1Item<'a> {
+    pub fn core(&self) -> &Item<'a> {
+        &self.item
+    }
+
+    pub fn lookahead(&self) -> &Symbol {
+        &self.lookahead
+    }
+}
+
+impl<'a> Show for LR1Item<'a> {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        fmtr.write_str(format!("[{}, {}]", self.item, self.lookahead).as_slice()).ok();
+        return Ok(());
+    }
+}
+
+/// A problem found by `Grammar::validate`, with where in the grammar text it
+/// was found when the grammar was read from one.
+#[deriving(Show, Clone, PartialEq)]
+pub enum Diagnostic {
+    /// The grammar has no productions and declares no start symbol.
+    NoStartSymbol,
+    /// A nonterminal used without any productions.
+    UndefinedNonTerm(String, Option<Position>),
+    /// A nonterminal no start symbol derives.
+    UnreachableNonTerm(String, Option<Position>),
+    /// A nonterminal that derives no string of terminals.
+    UnproductiveNonTerm(String, Option<Position>),
+    /// A terminal declared with `%token` or a precedence but never used.
+    UnusedTerm(String, Option<Position>),
+    /// `$` or `%empty` among the symbols of a production of the nonterminal,
+    /// which only a `GrammarBuilder` lets through.
+    MisplacedSymbol(String, Symbol)
+}
+
+impl Diagnostic {
+    /// Undefined and unproductive nonterminals make the grammar unusable,
+    /// the rest are warnings.
+    pub fn is_error(&self) -> bool {
+        match *self {
+            NoStartSymbol | UndefinedNonTerm(..) | UnproductiveNonTerm(..) | MisplacedSymbol(..) => { true }
+            UnreachableNonTerm(..) | UnusedTerm(..) => { false }
+        }
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        match *self {
+            NoStartSymbol | MisplacedSymbol(..) => { None }
+            UndefinedNonTerm(_, ref pos) => { pos.clone() }
+            UnreachableNonTerm(_, ref pos) => { pos.clone() }
+            UnproductiveNonTerm(_, ref pos) => { pos.clone() }
+            UnusedTerm(_, ref pos) => { pos.clone() }
+        }
+    }
+
+    /// The length of the text the diagnostic is about, for underlining.
+    pub fn len(&self) -> uint {
+        match *self {
+            NoStartSymbol | MisplacedSymbol(..) => { 1 }
+            UndefinedNonTerm(ref name, _) => { name.as_slice().char_len() }
+            UnreachableNonTerm(ref name, _) => { name.as_slice().char_len() }
+            UnproductiveNonTerm(ref name, _) => { name.as_slice().char_len() }
+            UnusedTerm(ref name, _) => { name.as_slice().char_len() }
+        }
+    }
+
+    /// The description prefixed with whether it is an error or a warning.
+    pub fn message(&self) -> String {
+        let kind = if self.is_error() { "error" } else { "warning" };
+        format!("{}: {}", kind, self.description())
+    }
+
+    pub fn description(&self) -> String {
+        match *self {
+            NoStartSymbol => {
+                "the grammar has no productions".to_string()
+            }
+            UndefinedNonTerm(ref name, _) => {
+                format!("nonterminal {} has no productions", name)
+            }
+            UnreachableNonTerm(ref name, _) => {
+                format!("nonterminal {} is unreachable from the start symbols", name)
+            }
+            UnproductiveNonTerm(ref name, _) => {
+                format!("nonterminal {} never derives a string of terminals", name)
+            }
+            UnusedTerm(ref name, _) => {
+                format!("terminal {} is declared but never used", name)
+            }
+            MisplacedSymbol(ref name, ref sym) => {
+                let written = match *sym {
+                    EndOfInput => { "$".to_string() }
+                    EmptyString => { "%empty alongside other symbols".to_string() }
+                    _ => { format!("{}", sym) }
+                };
+                format!("a production of {} uses {}", name, written)
+            }
+        }
+    }
+}
+
 pub struct Grammar {
-    start : String,
+    starts : Vec<String>,
+    augmented_starts : Vec<String>,
     symbols : HashSet<Symbol>,
-    first_cache : RefCell<HashMap<Symbol, SortedSet<Symbol>>>,
-    productions : HashMap<String, Vec<Production>>
+    analysis : RefCell<Option<Rc<GrammarAnalysis>>>,
+    productions : HashMap<String, Vec<Production>>,
+    precedence : HashMap<String, (uint, Assoc)>,
+    literals : TreeMap<String, String>,
+    declared : TreeSet<String>,
+    positions : HashMap<Symbol, Position>
 }
 
 impl Grammar {
-    pub fn new(root : Node) -> Grammar {
+    /// Reads a grammar from its text and validates it. The grammar is only
+    /// returned when `validate` finds no errors; warnings are left for the
+    /// caller to ask for.
+    pub fn from_str(text : &str) -> Result<Grammar, ::Error> {
+        let root = match parse(&mut gram_lexer(text)) {
+            Ok(root) => { root }
+            Err(err) => { return Err(::Parse(err)) }
+        };
+        Grammar::new(root).checked()
+    }
+
+    /// The grammar itself unless `validate` finds errors in it.
+    fn checked(self) -> Result<Grammar, ::Error> {
+        let diagnostics = self.validate();
+        if diagnostics.iter().any(|d| d.is_error()) {
+            return Err(::Invalid(diagnostics))
+        }
+        Ok(self)
+    }
+
+    pub fn new(mut root : Node) -> Grammar {
+        let literals = Grammar::name_literals(&mut root);
+        let mut positions : HashMap<Symbol, Position> = HashMap::new();
+        Grammar::first_positions(&root, &mut positions);
+        let mut declared : TreeSet<String> = TreeSet::new();
         let mut symbols : HashSet<Symbol> = HashSet::new();
-        let start = Grammar::name(Grammar::symbol(&*root.kids[0].kids[0]));
-        symbols.insert(NonTerm(start.clone()));
+        let mut starts : Vec<String> = Vec::new();
+        for node in root.kids.iter().filter(|n| n.label.as_slice() == "Start") {
+            for nt in node.kids.iter() {
+                let name = Grammar::name(Grammar::symbol(&**nt));
+                if !starts.contains(&name) {
+                    starts.push(name);
+                }
+            }
+        }
+        if starts.len() == 0 {
+            match root.kids.iter().find(|n| n.label.as_slice() == "Production") {
+                Some(first) => { starts.push(Grammar::name(Grammar::symbol(&*first.kids[0]))); }
+                None => {}
+            }
+        }
         let mut productions : HashMap<String, Vec<Production>> = HashMap::new();
+        let mut precedence : HashMap<String, (uint, Assoc)> = HashMap::new();
+        let mut level : uint = 0;
         for pnode in root.kids.iter() {
+            if pnode.label.as_slice() == "Start" {
+                continue
+            } else if pnode.label.as_slice() == "Token" {
+                for term in pnode.kids.iter() {
+                    declared.insert(Grammar::name(Grammar::symbol(&**term)));
+                }
+                continue
+            } else if pnode.label.as_slice() == "Precedence" {
+                // later declarations bind tighter
+                level += 1;
+                let assoc = match pnode.kids[0].label.as_slice() {
+                    "left" => { Left }
+                    "right" => { Right }
+                    _ => { NonAssoc }
+                };
+                for term in pnode.kids.slice_from(1).iter() {
+                    let name = Grammar::name(Grammar::symbol(&**term));
+                    declared.insert(name.clone());
+                    precedence.insert(name, (level, assoc.clone()));
+                }
+                continue
+            }
             let nt : String = Grammar::name(Grammar::symbol(&*pnode.kids[0]));
             let mut bodies : Vec<Production> = productions.pop(&nt).unwrap_or(Vec::new());
             for rules in pnode.kids[1].kids.iter() {
-                let mut body : Vec<Symbol> = Vec::new();
-                for n in rules.kids.iter() {
-                    let symbol = Grammar::symbol(&**n);
-                    body.push(symbol.clone());
-                    symbols.insert(symbol);
-                }
-                bodies.push(Production{nt: nt.clone(), symbols: body});
+                let (body, action, prec) = Grammar::rule(&**rules, &mut productions, &mut symbols);
+                let pos = rules.pos.clone().or(pnode.pos.clone());
+                bodies.push(Production{nt: nt.clone(), symbols: body, action: action, prec: prec, pos: pos});
             }
             productions.insert(nt, bodies);
         }
-        return Grammar{start:start, symbols: symbols, first_cache: RefCell::new(HashMap::new()), productions:productions}
+        Grammar::assemble(starts, productions, symbols, precedence, literals, declared, positions)
+    }
+
+    /// Finishes a grammar given its productions however they were written:
+    /// each start symbol gets its augmented production `S' -> S $`.
+    fn assemble(starts : Vec<String>, mut productions : HashMap<String, Vec<Production>>,
+                mut symbols : HashSet<Symbol>, precedence : HashMap<String, (uint, Assoc)>,
+                literals : TreeMap<String, String>, declared : TreeSet<String>,
+                positions : HashMap<Symbol, Position>) -> Grammar
+    {
+        for start in starts.iter() {
+            symbols.insert(NonTerm(start.clone()));
+        }
+        let augmented_starts = starts.iter().map(|start| Grammar::augment(&mut productions, start)).collect();
+        symbols.insert(EndOfInput);
+        return Grammar{
+            starts: starts,
+            augmented_starts: augmented_starts,
+            symbols: symbols,
+            analysis: RefCell::new(None),
+            productions:productions,
+            precedence: precedence,
+            literals: literals,
+            declared: declared,
+            positions: positions
+        }
+    }
+
+    /// Records where each terminal and nonterminal first appears in the tree.
+    fn first_positions(node : &Node, positions : &mut HashMap<Symbol, Position>) {
+        let label = node.label.as_slice();
+        if label == "Term" || label == "NonTerm" {
+            match node.pos {
+                Some(ref pos) => {
+                    let symbol = Grammar::symbol(node);
+                    if !positions.contains_key(&symbol) {
+                        positions.insert(symbol, pos.clone());
+                    }
+                }
+                None => {}
+            }
+            return
+        }
+        for kid in node.kids.iter() {
+            Grammar::first_positions(&**kid, positions);
+        }
+    }
+
+    /// Checks the grammar for undefined, unreachable and unproductive
+    /// nonterminals and for declared terminals that are never used. The
+    /// diagnostics are sorted by name within each kind.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics : Vec<Diagnostic> = Vec::new();
+        if self.starts.len() == 0 {
+            diagnostics.push(NoStartSymbol);
+        }
+        let mut names : Vec<&String> = self.productions.keys().filter(|name| {
+            !self.augmented_starts.contains(*name)
+        }).collect();
+        names.sort();
+
+        let mut used : TreeSet<Symbol> = TreeSet::new();
+        for productions in self.productions.values() {
+            for p in productions.iter() {
+                for sym in p.symbols.iter() {
+                    used.insert(sym.clone());
+                }
+                match p.prec {
+                    Some(ref term) => { used.insert(Term(term.clone())); }
+                    None => {}
+                }
+            }
+        }
+
+        for start in self.starts.iter() {
+            used.insert(NonTerm(start.clone()));
+        }
+        for sym in used.iter() {
+            match *sym {
+                NonTerm(ref name) if !self.productions.contains_key(name) => {
+                    diagnostics.push(UndefinedNonTerm(name.clone(), self.positions.find(sym).map(|p| p.clone())));
+                }
+                _ => {}
+            }
+        }
+
+        let mut reachable : HashSet<String> = HashSet::new();
+        let mut stack : Vec<String> = self.starts.clone();
+        while stack.len() > 0 {
+            let name = stack.pop().unwrap();
+            if reachable.contains(&name) {
+                continue
+            }
+            reachable.insert(name.clone());
+            let productions = match self.productions.find(&name) {
+                Some(productions) => { productions }
+                None => { continue }
+            };
+            for p in productions.iter() {
+                for sym in p.symbols.iter() {
+                    match *sym {
+                        NonTerm(ref next) if !reachable.contains(next) => { stack.push(next.clone()); }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for name in names.iter() {
+            if !reachable.contains(*name) {
+                diagnostics.push(UnreachableNonTerm((*name).clone(), self.definition(*name)));
+            }
+        }
+
+        let mut productive : HashSet<String> = HashSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (name, productions) in self.productions.iter() {
+                if productive.contains(name) {
+                    continue
+                }
+                let derives = productions.iter().any(|p| {
+                    p.symbols.iter().all(|sym| {
+                        match *sym {
+                            NonTerm(ref n) => { productive.contains(n) }
+                            _ => { true }
+                        }
+                    })
+                });
+                if derives {
+                    productive.insert(name.clone());
+                    changed = true;
+                }
+            }
+        }
+        for name in names.iter() {
+            if !productive.contains(*name) {
+                diagnostics.push(UnproductiveNonTerm((*name).clone(), self.definition(*name)));
+            }
+        }
+
+        for name in self.declared.iter() {
+            let term = Term(name.clone());
+            if !used.contains(&term) {
+                diagnostics.push(UnusedTerm(name.clone(), self.positions.find(&term).map(|p| p.clone())));
+            }
+        }
+        diagnostics
+    }
+
+    /// Where the first production of a nonterminal was written.
+    fn definition(&self, name : &String) -> Option<Position> {
+        self.productions.find(name).and_then(|ps| {
+            ps.iter().filter_map(|p| p.pos.clone()).min()
+        }).or(self.positions.find(&NonTerm(name.clone())).map(|p| p.clone()))
+    }
+
+    /// The body, action and `%prec` terminal of an alternative. EBNF items in
+    /// the body are replaced by nonterminals whose productions are added to
+    /// `productions`.
+    fn rule(rule : &Node, productions : &mut HashMap<String, Vec<Production>>, symbols : &mut HashSet<Symbol>) -> (Vec<Symbol>, Option<String>, Option<String>) {
+        let mut body : Vec<Symbol> = Vec::new();
+        let mut action : Option<String> = None;
+        let mut prec : Option<String> = None;
+        for n in rule.kids.iter() {
+            if n.label.as_slice() == "Action" {
+                action = Some(n.kids[0].label.clone());
+                continue
+            } else if n.label.as_slice() == "Prec" {
+                prec = Some(Grammar::name(Grammar::symbol(&*n.kids[0])));
+                continue
+            } else if n.label.as_slice() == "Empty" {
+                continue
+            }
+            let symbol = Grammar::ebnf_symbol(&**n, productions, symbols);
+            body.push(symbol.clone());
+            symbols.insert(symbol);
+        }
+        (body, action, prec)
+    }
+
+    /// Desugars an EBNF item into a fresh nonterminal named after the item
+    /// as it was written, e.g. `(COMMA Expr)*`, so diagnostics about it stay
+    /// readable. Writing the same item twice, actions and `%prec` included,
+    /// reuses the nonterminal.
+    ///
+    ///     X?  =>  N -> X | %empty
+    ///     X*  =>  N -> N X | %empty
+    ///     X+  =>  N -> N X | X
+    ///
+    /// where a group `(A | B)` contributes one alternative per branch.
+    fn ebnf_symbol(node : &Node, productions : &mut HashMap<String, Vec<Production>>, symbols : &mut HashSet<Symbol>) -> Symbol {
+        let label = node.label.as_slice();
+        if label == "Term" || label == "NonTerm" {
+            return Grammar::symbol(node)
+        }
+        let name = Grammar::ebnf_name(node);
+        if productions.contains_key(&name) {
+            return NonTerm(name)
+        }
+        // reserve the name, the alternatives may refer to it
+        productions.insert(name.clone(), Vec::new());
+        let inner : &Node = if label == "Group" { node } else { &*node.kids[0] };
+        let mut alternatives : Vec<(Vec<Symbol>, Option<String>, Option<String>)> = Vec::new();
+        if inner.label.as_slice() == "Group" {
+            for rule in inner.kids.iter() {
+                alternatives.push(Grammar::rule(&**rule, productions, symbols));
+            }
+        } else {
+            let symbol = Grammar::ebnf_symbol(inner, productions, symbols);
+            symbols.insert(symbol.clone());
+            alternatives.push((vec![symbol], None, None));
+        }
+        let mut bodies : Vec<Production> = Vec::new();
+        for (body, action, prec) in alternatives.into_iter() {
+            let mut rhs = body;
+            if label == "Repeat" || label == "Repeat1" {
+                rhs.insert(0, NonTerm(name.clone()));
+            }
+            bodies.push(Production{nt: name.clone(), symbols: rhs, action: action, prec: prec, pos: node.pos.clone()});
+        }
+        if label == "Repeat1" {
+            let once : Vec<Production> = bodies.iter().map(|p| {
+                let mut p = p.clone();
+                p.symbols.remove(0);
+                p
+            }).collect();
+            bodies.push_all_move(once);
+        } else if label == "Optional" || label == "Repeat" {
+            bodies.push(Production{nt: name.clone(), symbols: Vec::new(), action: None, prec: None, pos: node.pos.clone()});
+        }
+        productions.insert(name.clone(), bodies);
+        NonTerm(name)
+    }
+
+    /// The EBNF item as it would be written in the grammar. The actions and
+    /// `%prec` of a group are part of it, so groups differing only in those
+    /// get their own nonterminals.
+    fn ebnf_name(node : &Node) -> String {
+        match node.label.as_slice() {
+            "Term" | "NonTerm" => { node.kids[0].label.clone() }
+            "Action" => { format!("{{{}}}", node.kids[0].label) }
+            "Prec" => { format!("%prec {}", Grammar::ebnf_name(&*node.kids[0])) }
+            "Optional" => { format!("{}?", Grammar::ebnf_name(&*node.kids[0])) }
+            "Repeat" => { format!("{}*", Grammar::ebnf_name(&*node.kids[0])) }
+            "Repeat1" => { format!("{}+", Grammar::ebnf_name(&*node.kids[0])) }
+            "Empty" => { "%empty".to_string() }
+            "Group" => {
+                let rules : Vec<String> = node.kids.iter().map(|rule| {
+                    let items : Vec<String> = rule.kids.iter().map(|n| Grammar::ebnf_name(&**n)).collect();
+                    items.connect(" ")
+                }).collect();
+                format!("({})", rules.connect(" | "))
+            }
+            _ => { fail!(format!("Unexpected Node {}", node)) }
+        }
+    }
+
+    /// The start symbols, from the `%start` declarations or else the
+    /// nonterminal of the first production. Each start symbol has its own
+    /// initial and accepting states in the automata.
+    pub fn starts(&self) -> &Vec<String> {
+        &self.starts
+    }
+
+    /// The terminal names given to the quoted literals, keyed by the text of
+    /// the literal.
+    pub fn literals(&self) -> &TreeMap<String, String> {
+        &self.literals
+    }
+
+    /// The terminals used in a production or declared, sorted by name.
+    pub fn terminals(&self) -> Vec<String> {
+        let mut names : TreeSet<String> = self.declared.clone();
+        for sym in self.symbols.iter() {
+            match *sym {
+                Term(ref name) => { names.insert(name.clone()); }
+                _ => {}
+            }
+        }
+        names.into_iter().collect()
+    }
+
+    /// The nonterminals with productions, sorted by name. The augmented
+    /// start symbols are left out.
+    pub fn nonterminals(&self) -> Vec<String> {
+        let mut names : Vec<String> = self.productions.keys().filter(|name| {
+            !self.augmented_starts.contains(*name)
+        }).map(|name| name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    /// The productions of `nonterminals()` in that order, each nonterminal's
+    /// alternatives in the order they were written.
+    pub fn productions<'a>(&'a self) -> Vec<&'a Production> {
+        let mut productions : Vec<&'a Production> = Vec::new();
+        for name in self.nonterminals().iter() {
+            for p in self.productions[*name].iter() {
+                productions.push(p);
+            }
+        }
+        productions
+    }
+
+    /// Turns every quoted literal in the tree into a terminal and returns the
+    /// names chosen for them. The same literal always gets the same name and
+    /// a name never collides with a terminal written out in the grammar.
+    fn name_literals(root : &mut Node) -> TreeMap<String, String> {
+        let mut taken : HashSet<String> = HashSet::new();
+        Grammar::collect_terms(root, &mut taken);
+        let mut literals : TreeMap<String, String> = TreeMap::new();
+        Grammar::rename_literals(root, &mut taken, &mut literals);
+        literals
+    }
+
+    fn collect_terms(node : &Node, terms : &mut HashSet<String>) {
+        if node.label.as_slice() == "Term" {
+            terms.insert(node.kids[0].label.clone());
+        }
+        for kid in node.kids.iter() {
+            Grammar::collect_terms(&**kid, terms);
+        }
+    }
+
+    fn rename_literals(node : &mut Node, taken : &mut HashSet<String>, literals : &mut TreeMap<String, String>) {
+        if node.label.as_slice() == "Literal" {
+            let literal = node.kids[0].label.clone();
+            let name = match literals.find(&literal) {
+                Some(name) => { name.clone() }
+                None => {
+                    let base = Grammar::literal_name(literal.as_slice());
+                    let mut name = base.clone();
+                    let mut n : uint = 1;
+                    while taken.contains(&name) {
+                        n += 1;
+                        name = format!("{}_{}", base, n);
+                    }
+                    taken.insert(name.clone());
+                    literals.insert(literal.clone(), name.clone());
+                    name
+                }
+            };
+            node.label = "Term".to_string();
+            node.kids.get_mut(0).label = name;
+            return
+        }
+        for kid in node.kids.iter_mut() {
+            Grammar::rename_literals(&mut **kid, taken, literals);
+        }
+    }
+
+    /// A terminal name for a literal: words are upper cased and punctuation
+    /// is spelled out, so `"+="` becomes `PLUS_EQ` and `"if"` becomes `IF`.
+    fn literal_name(literal : &str) -> String {
+        let mut parts : Vec<String> = Vec::new();
+        let mut word = String::new();
+        for ch in literal.chars() {
+            if ch.is_alphanumeric() || ch == '_' {
+                word.push(ch.to_uppercase());
+                continue
+            }
+            if word.len() > 0 {
+                parts.push(word);
+                word = String::new();
+            }
+            parts.push(match ch {
+                '+' => { "PLUS".to_string() }
+                '-' => { "DASH".to_string() }
+                '*' => { "STAR".to_string() }
+                '/' => { "SLASH".to_string() }
+                '%' => { "PERCENT".to_string() }
+                '(' => { "LPAREN".to_string() }
+                ')' => { "RPAREN".to_string() }
+                '[' => { "LSQUARE".to_string() }
+                ']' => { "RSQUARE".to_string() }
+                '{' => { "LBRACE".to_string() }
+                '}' => { "RBRACE".to_string() }
+                '<' => { "LT".to_string() }
+                '>' => { "GT".to_string() }
+                '=' => { "EQ".to_string() }
+                '!' => { "BANG".to_string() }
+                '&' => { "AMP".to_string() }
+                '|' => { "BAR".to_string() }
+                '^' => { "CARET".to_string() }
+                '~' => { "TILDE".to_string() }
+                '?' => { "QUESTION".to_string() }
+                ':' => { "COLON".to_string() }
+                ';' => { "SEMI".to_string() }
+                ',' => { "COMMA".to_string() }
+                '.' => { "DOT".to_string() }
+                '@' => { "AT".to_string() }
+                '#' => { "HASH".to_string() }
+                '$' => { "DOLLAR".to_string() }
+                '\'' => { "QUOTE".to_string() }
+                '"' => { "DQUOTE".to_string() }
+                '\\' => { "BACKSLASH".to_string() }
+                ' ' => { "SPACE".to_string() }
+                _ => { format!("U{:X}", ch as u32) }
+            });
+        }
+        if word.len() > 0 {
+            parts.push(word);
+        }
+        if parts.len() == 0 {
+            return "EMPTY_LITERAL".to_string();
+        }
+        parts.connect("_")
+    }
+
+    /// The precedence level and associativity declared for a terminal.
+    pub fn precedence(&self, term : &String) -> Option<(uint, Assoc)> {
+        self.precedence.find(term).map(|&(level, ref assoc)| (level, assoc.clone()))
+    }
+
+    /// The precedence of a production is that of its `%prec` terminal or,
+    /// without one, of the last terminal in its body. Returns the terminal
+    /// along with its level.
+    pub fn production_precedence(&self, production : &Production) -> Option<(String, uint)> {
+        let term = match production.prec {
+            Some(ref term) => { Some(term.clone()) }
+            None => {
+                production.symbols.iter().rev().filter_map(|sym| {
+                    match *sym {
+                        Term(ref name) => { Some(name.clone()) }
+                        _ => { None }
+                    }
+                }).next()
+            }
+        };
+        term.and_then(|term| {
+            self.precedence(&term).map(|(level, _)| (term.clone(), level))
+        })
+    }
+
+    /// Adds the production `S' -> S $` to the productions, where `S` is the
+    /// start symbol and `$` marks the end of the input. Returns the name of
+    /// the new start symbol `S'`.
+    fn augment(productions : &mut HashMap<String, Vec<Production>>, start : &String) -> String {
+        let mut name = format!("{}'", start);
+        while productions.contains_key(&name) {
+            name.push_str("'");
+        }
+        let production = Production{
+            nt: name.clone(),
+            symbols: vec![NonTerm(start.clone()), EndOfInput],
+            action: None,
+            prec: None,
+            pos: None
+        };
+        productions.insert(name.clone(), vec![production]);
+        name
     }
 
     fn symbol<'b>(node : &'b Node) -> Symbol {
@@ -300,105 +984,50 @@ impl Grammar {
             Term(s) => { s }
             NonTerm(s) => { s }
             EmptyString => { "".to_string() }
+            EndOfInput => { "$".to_string() }
         }
     }
 
+    /// The NULLABLE, FIRST and FOLLOW sets of the grammar. They are computed
+    /// the first time they are asked for and shared afterwards.
+    pub fn analysis(&self) -> Rc<GrammarAnalysis> {
+        if self.analysis.borrow().is_none() {
+            let analysis = Rc::new(GrammarAnalysis::new(self));
+            *self.analysis.borrow_mut() = Some(analysis);
+        }
+        self.analysis.borrow().as_ref().unwrap().clone()
+    }
+
     #[allow(non_snake_case)]
     pub fn FIRST(&self, sym : Symbol) -> SortedSet<Symbol> {
-        println!("FIRST {}", sym);
-        if self.first_cache.borrow().contains_key(&sym) {
-            return self.first_cache.borrow().deref()[sym].clone();
-        }
-        {
-            let mut cache = self.first_cache.borrow_mut();
-            cache.insert(sym.clone(), SortedSet::new());
-        }
-        let symbols = match sym.clone() {
-            EmptyString => {
-                SortedSet::singleton(sym.clone())
-            }
-            Term(_) => {
-                SortedSet::singleton(sym.clone())
-            }
-            NonTerm(ref name) => {
-                let mut symbols : SortedSet<Symbol> = SortedSet::new();
-                for production in self.productions[name.clone()].clone().iter() {
-                    let mut all_have_e = true;
-                    for symbol in production.symbols.iter() {
-                        if &sym == symbol {
-                            continue;
-                        }
-                        let mut first = self.FIRST(symbol.clone());
-                        symbols.addall(first.minus(&SortedSet::singleton(EmptyString)));
-                        if first.contains(&EmptyString) {
-                            all_have_e = false;
-                            break;
-                        }
-                    }
-                    if all_have_e {
-                        symbols.add(EmptyString)
-                    }
-                }
-                symbols
-            }
-            };
-        let mut cache = self.first_cache.borrow_mut();
-        cache.insert(sym.clone(), symbols.clone());
-        symbols
+        self.analysis().first(&sym)
     }
 
     #[allow(non_snake_case)]
     pub fn FIRST_vec(&self, syms : &Vec<Symbol>) -> SortedSet<Symbol> {
-        let mut symbols : SortedSet<Symbol> = SortedSet::new();
-        for sym in syms.iter() {
-            let mut first = self.FIRST(sym.clone());
-            symbols.addall(first.minus(&SortedSet::singleton(EmptyString)));
-            if first.contains(&EmptyString) {
-                return symbols;
-            }
-        }
-        symbols.add(EmptyString);
-        symbols
+        self.analysis().first_vec(syms.as_slice())
     }
 
     #[allow(non_snake_case)]
     pub fn FOLLOW(&self, nt : Symbol) -> SortedSet<Symbol> {
-        println!("FOLLOW {}", nt);
-        let mut symbols : SortedSet<Symbol> = SortedSet::new();
         match nt {
-            NonTerm(ref name) => {
-                for p in self.productions[name.clone()].iter() {
-                    let i : uint = match p.index_of(&nt) {
-                        Some(o) => { o }
-                        None => { continue; }
-                    };
-                    if i + 1 < p.symbols.len() {
-                        let mut first = self.FIRST_vec(&p.symbols);
-                        if first.contains(&EmptyString) {
-                            first = first.minus(&SortedSet::singleton(EmptyString));
-                            if p.nt.as_slice() != name.as_slice() {
-                                symbols.addall(self.FOLLOW(NonTerm(p.nt.clone())));
-                            }
-                        }
-                        symbols.addall(first);
-                    } else if i + 1 == p.symbols.len() && p.nt.as_slice() != name.as_slice() {
-                        symbols.addall(self.FOLLOW(NonTerm(p.nt.clone())));
-                    }
-                }
-            }
+            NonTerm(ref name) => { self.analysis().follow(name) }
             _ => { fail!("Must pass in a NonTerm to FOLLOW"); }
         }
-        symbols
     }
 
-
     #[allow(non_snake_case)]
-    pub fn LR0_automaton<'a>(&'a self) -> SLRAutomaton<'a> {
-        let mut A = SLRAutomaton{grammar: self, states: Vec::new()};
+    pub fn lr0_automaton<'a>(&'a self) -> SLRAutomaton<'a> {
+        let mut A = SLRAutomaton{grammar: self, states: Vec::new(), starts: Vec::new()};
         let mut states : TreeMap<SortedSet<Item<'a>>,uint> = TreeMap::new();
         let mut stack : Vec<SortedSet<Item<'a>>> = Vec::new();
         let mut next_id : uint = 0;
-        stack.push(self.closure(&self.start_items()));
+        let initial : Vec<SortedSet<Item<'a>>> = range(0, self.starts.len()).map(|k| {
+            self.closure(&self.start_items(k))
+        }).collect();
+        for items in initial.iter().rev() {
+            stack.push(items.clone());
+        }
 
         while stack.len() > 0 {
             let items = stack.pop().unwrap();
@@ -431,17 +1060,27 @@ impl Grammar {
                 }
             }
         }
+        for (start, items) in self.starts.iter().zip(initial.iter()) {
+            A.starts.push((start.clone(), states[*items]));
+        }
         return A;
     }
 
-    fn start_items<'a>(&'a self) -> SortedSet<Item<'a>> {
+    /// The items `S' -> . S $` of the `k`th start symbol.
+    fn start_items<'a>(&'a self, k : uint) -> SortedSet<Item<'a>> {
         let mut items : SortedSet<Item<'a>> = SortedSet::new();
-        for production in self.productions[self.start].iter() {
+        for production in self.productions[self.augmented_starts[k]].iter() {
             items.add(Item{production: production, dot: 0});
         }
         return items;
     }
 
+    /// Whether an item belongs to the kernel of its state rather than having
+    /// been added by the closure: its dot has moved, or it is a start item.
+    pub fn is_kernel(&self, item : &Item) -> bool {
+        item.dot > 0 || self.augmented_starts.contains(&item.production.nt)
+    }
+
     pub fn closure<'a>(&'a self, items : &SortedSet<Item<'a>>) -> SortedSet<Item<'a>> {
         let mut ret : SortedSet<Item<'a>> = SortedSet::new();
         let mut stack : Vec<Item> = Vec::new();
@@ -457,10 +1096,14 @@ impl Grammar {
                         EmptyString => {
                             continue
                         }
-                        Term(_) => {
+                        Term(_) | EndOfInput => {
                             continue
                         } NonTerm(ref name) => {
-                            self.productions.find(name).unwrap()
+                            // undefined nonterminals are reported by validate
+                            match self.productions.find(name) {
+                                Some(prods) => { prods }
+                                None => { continue }
+                            }
                         }};
                 for prod in prods.iter() {
                     let next_item = Item{production: prod, dot: 0};
@@ -491,61 +1134,1094 @@ impl Grammar {
         return ret
 
     }
-}
 
-#[deriving(Show)]
-pub struct SLRState<'a> {
-    id : uint,
-    items : SortedSet<Item<'a>>,
-    moves : HashMap<Symbol, uint>
-}
+    /// Builds the canonical LR(1) automaton: states are sets of LR(1) items
+    /// and two states are only the same when their items and lookaheads are.
+    #[allow(non_snake_case)]
+    pub fn lr1_automaton<'a>(&'a self) -> LR1Automaton<'a> {
+        let mut A = LR1Automaton{grammar: self, states: Vec::new(), starts: Vec::new()};
+        let mut states : TreeMap<SortedSet<LR1Item<'a>>,uint> = TreeMap::new();
+        for (k, name) in self.starts.iter().enumerate() {
+            let start = self.lr1_closure(&self.lr1_start_items(k));
+            states.insert(start.clone(), k);
+            A.states.push(LR1State{id: k, items: start, moves: HashMap::new()});
+            A.starts.push((name.clone(), k));
+        }
 
-pub struct SLRAutomaton<'a> {
-    grammar : &'a Grammar,
-    states : Vec<SLRState<'a>>
-}
+        let mut i = 0;
+        while i < A.states.len() {
+            let moves = self.lr1_moves(&A.states[i].items);
+            for (sym, next) in moves.into_iter() {
+                let id = match states.find(&next) {
+                    Some(id) => { *id }
+                    None => {
+                        let id = A.states.len();
+                        states.insert(next.clone(), id);
+                        A.states.push(LR1State{id: id, items: next, moves: HashMap::new()});
+                        id
+                    }
+                };
+                A.states.get_mut(i).moves.insert(sym, id);
+            }
+            i += 1;
+        }
+        return A;
+    }
 
-#[deriving(Show)]
-pub enum LRAction<'a> {
-    Goto(uint),
-    Shift(uint),
+    /// Builds a minimal LR(1) automaton with Pager's weak compatibility test.
+    /// States are identified by their LR(0) kernel, as in the LR(0) and
+    /// LALR(1) automata, and two states with the same kernel are merged when
+    /// their lookaheads are weakly compatible, which guarantees the merge
+    /// introduces no conflict canonical LR(1) would not have. The test is
+    /// only sufficient, so a state may be split even where LALR(1) merging
+    /// would have been harmless: the result has at least the LR(0) states
+    /// and at most the canonical LR(1) ones.
+    #[allow(non_snake_case)]
+    pub fn minimal_lr1_automaton<'a>(&'a self) -> LR1Automaton<'a> {
+        let mut kernels : Vec<TreeMap<Item<'a>, SortedSet<Symbol>>> = Vec::new();
+        let mut moves : Vec<HashMap<Symbol, uint>> = Vec::new();
+        let mut by_core : TreeMap<SortedSet<Item<'a>>, Vec<uint>> = TreeMap::new();
+        let mut stack : Vec<uint> = Vec::new();
+
+        for k in range(0, self.starts.len()) {
+            let mut start : TreeMap<Item<'a>, SortedSet<Symbol>> = TreeMap::new();
+            for item in self.start_items(k).into_iter() {
+                start.insert(item, SortedSet::singleton(EndOfInput));
+            }
+            by_core.insert(Grammar::kernel_core(&start), vec![k]);
+            kernels.push(start);
+            moves.push(HashMap::new());
+            stack.push(k);
+        }
+
+        while stack.len() > 0 {
+            let i = stack.pop().unwrap();
+            let closure = self.lr1_closure(&Grammar::kernel_items(&kernels[i]));
+            for (sym, kernel) in self.lr1_successors(&closure).into_iter() {
+                let core = Grammar::kernel_core(&kernel);
+                let candidates = by_core.find(&core).map(|c| c.clone()).unwrap_or(Vec::new());
+                let compatible = candidates.iter().map(|j| *j).find(|j| {
+                    Grammar::weakly_compatible(&kernels[*j], &kernel)
+                });
+                let target = match compatible {
+                    Some(j) => {
+                        let mut grew = false;
+                        let mut merged = kernels[j].clone();
+                        for (item, lookaheads) in kernel.into_iter() {
+                            let mut set = merged.pop(&item).unwrap_or(SortedSet::new());
+                            let before = set.len();
+                            set.addall(lookaheads);
+                            grew = grew || set.len() != before;
+                            merged.insert(item, set);
+                        }
+                        if grew {
+                            *kernels.get_mut(j) = merged;
+                            if !stack.contains(&j) {
+                                stack.push(j);
+                            }
+                        }
+                        j
+                    }
+                    None => {
+                        let j = kernels.len();
+                        kernels.push(kernel);
+                        moves.push(HashMap::new());
+                        let mut ids = by_core.pop(&core).unwrap_or(Vec::new());
+                        ids.push(j);
+                        by_core.insert(core, ids);
+                        stack.push(j);
+                        j
+                    }
+                };
+                moves.get_mut(i).insert(sym, target);
+            }
+        }
+
+        // A state whose lookaheads grew may have moved its transitions to
+        // states split off later, leaving the old targets unreachable.
+        let mut ids : HashMap<uint, uint> = HashMap::new();
+        let mut order : Vec<uint> = range(0, self.starts.len()).collect();
+        for k in range(0, self.starts.len()) {
+            ids.insert(k, k);
+        }
+        let mut k = 0;
+        while k < order.len() {
+            let i = order[k];
+            for (_, target) in moves[i].iter() {
+                if !ids.contains_key(target) {
+                    ids.insert(*target, order.len());
+                    order.push(*target);
+                }
+            }
+            k += 1;
+        }
+
+        let mut A = LR1Automaton{grammar: self, states: Vec::new(), starts: Vec::new()};
+        for (k, name) in self.starts.iter().enumerate() {
+            A.starts.push((name.clone(), k));
+        }
+        for (id, i) in order.iter().enumerate() {
+            let mut state_moves : HashMap<Symbol, uint> = HashMap::new();
+            for (sym, target) in moves[*i].iter() {
+                state_moves.insert(sym.clone(), ids[*target]);
+            }
+            A.states.push(LR1State{
+                id: id,
+                items: self.lr1_closure(&Grammar::kernel_items(&kernels[*i])),
+                moves: state_moves
+            });
+        }
+        return A;
+    }
+
+    /// The kernels of the states reached from an LR(1) item set, with the
+    /// lookaheads of each kernel item.
+    fn lr1_successors<'a>(&'a self, items : &SortedSet<LR1Item<'a>>) -> HashMap<Symbol, TreeMap<Item<'a>, SortedSet<Symbol>>> {
+        let mut ret : HashMap<Symbol, TreeMap<Item<'a>, SortedSet<Symbol>>> = HashMap::new();
+        for item in items.iter() {
+            if item.item.dot >= item.item.production.symbols.len() {
+                continue
+            }
+            let ref sym = item.item.production.symbols[item.item.dot];
+            let mut kernel = ret.pop(sym).unwrap_or(TreeMap::new());
+            let next = Item{production: item.item.production, dot: item.item.dot+1};
+            let mut lookaheads = kernel.pop(&next).unwrap_or(SortedSet::new());
+            lookaheads.add(item.lookahead.clone());
+            kernel.insert(next, lookaheads);
+            ret.insert(sym.clone(), kernel);
+        }
+        return ret
+    }
+
+    fn kernel_core<'a>(kernel : &TreeMap<Item<'a>, SortedSet<Symbol>>) -> SortedSet<Item<'a>> {
+        let mut core : SortedSet<Item<'a>> = SortedSet::new();
+        for item in kernel.keys() {
+            core.add(item.clone());
+        }
+        core
+    }
+
+    fn kernel_items<'a>(kernel : &TreeMap<Item<'a>, SortedSet<Symbol>>) -> SortedSet<LR1Item<'a>> {
+        let mut items : SortedSet<LR1Item<'a>> = SortedSet::new();
+        for (item, lookaheads) in kernel.iter() {
+            for lookahead in lookaheads.iter() {
+                items.add(LR1Item{item: item.clone(), lookahead: lookahead.clone()});
+            }
+        }
+        items
+    }
+
+    /// Pager's weak compatibility of two kernels with the same core. With
+    /// lookaheads `a` and `b` for the kernel items, the kernels may be merged
+    /// when for every pair of items `i` and `j` either `a[i]` is disjoint
+    /// from `b[j]` and `b[i]` from `a[j]`, or the items already share a
+    /// lookahead within one of the kernels.
+    fn weakly_compatible<'a>(a : &TreeMap<Item<'a>, SortedSet<Symbol>>, b : &TreeMap<Item<'a>, SortedSet<Symbol>>) -> bool {
+        let a_sets : Vec<&SortedSet<Symbol>> = a.values().collect();
+        let b_sets : Vec<&SortedSet<Symbol>> = b.values().collect();
+        for i in range(0, a_sets.len()) {
+            for j in range(i + 1, a_sets.len()) {
+                if !a_sets[i].intersects(b_sets[j]) && !b_sets[i].intersects(a_sets[j]) {
+                    continue
+                }
+                if a_sets[i].intersects(a_sets[j]) || b_sets[i].intersects(b_sets[j]) {
+                    continue
+                }
+                return false
+            }
+        }
+        true
+    }
+
+    fn lr1_start_items<'a>(&'a self, k : uint) -> SortedSet<LR1Item<'a>> {
+        let mut items : SortedSet<LR1Item<'a>> = SortedSet::new();
+        for item in self.start_items(k).into_iter() {
+            items.add(LR1Item{item: item, lookahead: EndOfInput});
+        }
+        return items;
+    }
+
+    /// The LR(1) closure: an item `[A -> a . B b, x]` adds `[B -> . g, y]`
+    /// for every production of `B` and every terminal `y` in FIRST(b x).
+    pub fn lr1_closure<'a>(&'a self, items : &SortedSet<LR1Item<'a>>) -> SortedSet<LR1Item<'a>> {
+        let analysis = self.analysis();
+        let mut ret : SortedSet<LR1Item<'a>> = SortedSet::new();
+        let mut stack : Vec<LR1Item<'a>> = Vec::new();
+        for item in items.iter() {
+            stack.push((*item).clone());
+        }
+        while stack.len() > 0 {
+            let item : LR1Item<'a> = stack.pop().unwrap();
+            if ret.contains(&item) {
+                continue
+            }
+            ret.add(item.clone());
+            let production = item.item.production;
+            if item.item.dot >= production.symbols.len() {
+                continue
+            }
+            let name = match production.symbols[item.item.dot] {
+                NonTerm(ref name) => { name }
+                _ => { continue }
+            };
+            let prods = match self.productions.find(name) {
+                Some(prods) => { prods }
+                None => { continue }
+            };
+            let mut rest = production.symbols.slice_from(item.item.dot + 1).to_vec();
+            rest.push(item.lookahead.clone());
+            let lookaheads = analysis.first_vec(rest.as_slice());
+            for prod in prods.iter() {
+                for lookahead in lookaheads.iter() {
+                    let next_item = LR1Item{
+                        item: Item{production: prod, dot: 0},
+                        lookahead: lookahead.clone()
+                    };
+                    if !ret.contains(&next_item) {
+                        stack.push(next_item);
+                    }
+                }
+            }
+        }
+        return ret
+    }
+
+    #[allow(non_snake_case)]
+    pub fn lr1_moves<'a>(&'a self, I : &SortedSet<LR1Item<'a>>) -> HashMap<Symbol, SortedSet<LR1Item<'a>>> {
+        let mut kernels : HashMap<Symbol,SortedSet<LR1Item<'a>>> = HashMap::new();
+        for item in I.iter() {
+            if item.item.dot >= item.item.production.symbols.len() {
+                continue
+            }
+            let ref sym = item.item.production.symbols[item.item.dot];
+            let mut kernel = kernels.pop(sym).unwrap_or(SortedSet::new());
+            kernel.add(LR1Item{
+                item: Item{production: item.item.production, dot: item.item.dot+1},
+                lookahead: item.lookahead.clone()
+            });
+            kernels.insert(sym.clone(), kernel);
+        }
+        let mut ret : HashMap<Symbol,SortedSet<LR1Item<'a>>> = HashMap::new();
+        for (sym, kernel) in kernels.into_iter() {
+            ret.insert(sym, self.lr1_closure(&kernel));
+        }
+        return ret
+    }
+}
+
+/// A nonterminal, for the bodies given to `GrammarBuilder::rule`.
+pub fn nt(name : &str) -> Symbol {
+    NonTerm(name.to_string())
+}
+
+/// A terminal, for the bodies given to `GrammarBuilder::rule`.
+pub fn t(name : &str) -> Symbol {
+    Term(name.to_string())
+}
+
+/// Builds a `Grammar` in code rather than from text. Each method stands for
+/// the grammar file construct of the same name:
+///
+///     let grammar = GrammarBuilder::new()
+///         .left(&["PLUS"])
+///         .left(&["STAR"])
+///         .rule("Expr", &[nt("Expr"), t("PLUS"), nt("Expr")])
+///         .rule("Expr", &[nt("Expr"), t("STAR"), nt("Expr")])
+///         .rule("Expr", &[t("NUMBER")]).action("$$ = $1;")
+///         .build()
+///         .unwrap();
+///
+/// As in a grammar file the start symbol is the first rule's nonterminal
+/// unless `start` is given, and precedence declarations made later bind
+/// tighter.
+pub struct GrammarBuilder {
+    starts : Vec<String>,
+    productions : Vec<Production>,
+    declared : TreeSet<String>,
+    precedence : HashMap<String, (uint, Assoc)>,
+    level : uint
+}
+
+impl GrammarBuilder {
+    pub fn new() -> GrammarBuilder {
+        GrammarBuilder{
+            starts: Vec::new(),
+            productions: Vec::new(),
+            declared: TreeSet::new(),
+            precedence: HashMap::new(),
+            level: 0
+        }
+    }
+
+    /// Adds a start symbol, as `%start` does.
+    pub fn start(mut self, name : &str) -> GrammarBuilder {
+        let name = name.to_string();
+        if !self.starts.contains(&name) {
+            self.starts.push(name);
+        }
+        self
+    }
+
+    /// Adds the alternative `name -> body`. An empty body, or one of just
+    /// `EmptyString`, derives the empty string. `EndOfInput`, and
+    /// `EmptyString` next to other symbols, are reported by `build`.
+    pub fn rule(mut self, name : &str, body : &[Symbol]) -> GrammarBuilder {
+        let symbols = if body == [EmptyString].as_slice() { Vec::new() } else { body.to_vec() };
+        self.productions.push(Production{
+            nt: name.to_string(),
+            symbols: symbols,
+            action: None,
+            prec: None,
+            pos: None
+        });
+        self
+    }
+
+    /// Gives the last rule added the precedence of `term`, as `%prec` does.
+    pub fn prec(mut self, term : &str) -> GrammarBuilder {
+        match self.productions.last_mut() {
+            Some(production) => { production.prec = Some(term.to_string()); }
+            None => { fail!("prec must follow a rule"); }
+        }
+        self
+    }
+
+    /// Attaches an action to the last rule added.
+    pub fn action(mut self, code : &str) -> GrammarBuilder {
+        match self.productions.last_mut() {
+            Some(production) => { production.action = Some(code.to_string()); }
+            None => { fail!("action must follow a rule"); }
+        }
+        self
+    }
+
+    /// Declares a terminal, as `%token` does.
+    pub fn token(mut self, name : &str) -> GrammarBuilder {
+        self.declared.insert(name.to_string());
+        self
+    }
+
+    pub fn left(self, terms : &[&str]) -> GrammarBuilder {
+        self.declare(Left, terms)
+    }
+
+    pub fn right(self, terms : &[&str]) -> GrammarBuilder {
+        self.declare(Right, terms)
+    }
+
+    pub fn nonassoc(self, terms : &[&str]) -> GrammarBuilder {
+        self.declare(NonAssoc, terms)
+    }
+
+    fn declare(mut self, assoc : Assoc, terms : &[&str]) -> GrammarBuilder {
+        self.level += 1;
+        for term in terms.iter() {
+            self.declared.insert(term.to_string());
+            self.precedence.insert(term.to_string(), (self.level, assoc.clone()));
+        }
+        self
+    }
+
+    /// The grammar, provided `Grammar::validate` finds no errors in it.
+    pub fn build(self) -> Result<Grammar, ::Error> {
+        let GrammarBuilder{starts, productions: list, declared, precedence, ..} = self;
+        let mut starts = starts;
+        if starts.len() == 0 {
+            match list.as_slice().head() {
+                Some(first) => { starts.push(first.nt.clone()); }
+                None => {}
+            }
+        }
+        let mut misplaced : Vec<Diagnostic> = Vec::new();
+        for production in list.iter() {
+            for sym in production.symbols.iter() {
+                match *sym {
+                    EndOfInput | EmptyString => { misplaced.push(MisplacedSymbol(production.nt.clone(), sym.clone())); }
+                    Term(_) | NonTerm(_) => {}
+                }
+            }
+        }
+        if misplaced.len() > 0 {
+            return Err(::Invalid(misplaced))
+        }
+        let mut symbols : HashSet<Symbol> = HashSet::new();
+        let mut productions : HashMap<String, Vec<Production>> = HashMap::new();
+        for production in list.into_iter() {
+            for sym in production.symbols.iter() {
+                symbols.insert(sym.clone());
+            }
+            let mut bodies = productions.pop(&production.nt).unwrap_or(Vec::new());
+            let name = production.nt.clone();
+            bodies.push(production);
+            productions.insert(name, bodies);
+        }
+        Grammar::assemble(starts, productions, symbols, precedence, TreeMap::new(), declared, HashMap::new()).checked()
+    }
+}
+
+/// NULLABLE, FIRST and FOLLOW for every nonterminal of a grammar, computed by
+/// iterating each set to a fixed point. FIRST sets hold `EmptyString` when
+/// the symbol (or string of symbols) can derive the empty string.
+pub struct GrammarAnalysis {
+    nullable : HashSet<String>,
+    first : HashMap<String, SortedSet<Symbol>>,
+    follow : HashMap<String, SortedSet<Symbol>>
+}
+
+impl GrammarAnalysis {
+    pub fn new(grammar : &Grammar) -> GrammarAnalysis {
+        let mut analysis = GrammarAnalysis{
+            nullable : HashSet::new(),
+            first : HashMap::new(),
+            follow : HashMap::new()
+        };
+        analysis.compute_nullable(grammar);
+        analysis.compute_first(grammar);
+        analysis.compute_follow(grammar);
+        analysis
+    }
+
+    pub fn nullable(&self, sym : &Symbol) -> bool {
+        match *sym {
+            EmptyString => { true }
+            Term(_) | EndOfInput => { false }
+            NonTerm(ref name) => { self.nullable.contains(name) }
+        }
+    }
+
+    pub fn nullable_vec(&self, syms : &[Symbol]) -> bool {
+        syms.iter().all(|sym| self.nullable(sym))
+    }
+
+    pub fn first(&self, sym : &Symbol) -> SortedSet<Symbol> {
+        let mut symbols = match *sym {
+            EmptyString => { SortedSet::new() }
+            Term(_) | EndOfInput => { SortedSet::singleton(sym.clone()) }
+            NonTerm(ref name) => {
+                self.first.find(name).map(|s| s.clone()).unwrap_or(SortedSet::new())
+            }
+        };
+        if self.nullable(sym) {
+            symbols.add(EmptyString);
+        }
+        symbols
+    }
+
+    pub fn first_vec(&self, syms : &[Symbol]) -> SortedSet<Symbol> {
+        let mut symbols : SortedSet<Symbol> = SortedSet::new();
+        for sym in syms.iter() {
+            symbols.addall(self.first(sym).minus(&SortedSet::singleton(EmptyString)));
+            if !self.nullable(sym) {
+                return symbols;
+            }
+        }
+        symbols.add(EmptyString);
+        symbols
+    }
+
+    pub fn follow(&self, nt : &String) -> SortedSet<Symbol> {
+        self.follow.find(nt).map(|s| s.clone()).unwrap_or(SortedSet::new())
+    }
+
+    fn compute_nullable(&mut self, grammar : &Grammar) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (name, productions) in grammar.productions.iter() {
+                if self.nullable.contains(name) {
+                    continue
+                }
+                if productions.iter().any(|p| self.nullable_vec(p.symbols.as_slice())) {
+                    self.nullable.insert(name.clone());
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn compute_first(&mut self, grammar : &Grammar) {
+        for name in grammar.productions.keys() {
+            self.first.insert(name.clone(), SortedSet::new());
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (name, productions) in grammar.productions.iter() {
+                let mut symbols = self.first[*name].clone();
+                let before = symbols.len();
+                for p in productions.iter() {
+                    symbols.addall(self.first_vec(p.symbols.as_slice()).minus(&SortedSet::singleton(EmptyString)));
+                }
+                if symbols.len() != before {
+                    changed = true;
+                    self.first.insert(name.clone(), symbols);
+                }
+            }
+        }
+    }
+
+    /// Every occurrence of a nonterminal `B` in a body `A -> a B b` adds
+    /// FIRST(b) to FOLLOW(B) and, when `b` is nullable, FOLLOW(A) as well.
+    fn compute_follow(&mut self, grammar : &Grammar) {
+        for name in grammar.productions.keys() {
+            self.follow.insert(name.clone(), SortedSet::new());
+        }
+        for start in grammar.starts.iter() {
+            self.follow.insert(start.clone(), SortedSet::singleton(EndOfInput));
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for productions in grammar.productions.values() {
+                for p in productions.iter() {
+                    for (i, sym) in p.symbols.iter().enumerate() {
+                        let name = match *sym {
+                            NonTerm(ref name) => { name }
+                            _ => { continue }
+                        };
+                        let rest = p.symbols.slice_from(i + 1);
+                        let mut additions = self.first_vec(rest).minus(&SortedSet::singleton(EmptyString));
+                        if self.nullable_vec(rest) {
+                            additions.addall(self.follow(&p.nt));
+                        }
+                        let mut symbols = self.follow.pop(name).unwrap_or(SortedSet::new());
+                        let before = symbols.len();
+                        symbols.addall(additions);
+                        if symbols.len() != before {
+                            changed = true;
+                        }
+                        self.follow.insert(name.clone(), symbols);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An automaton built from a grammar, from which a parse table is read.
+pub trait Automaton<'a> : Show {
+    fn grammar(&self) -> &'a Grammar;
+
+    /// The number of states.
+    fn len(&self) -> uint;
+
+    /// The start symbols with their initial states.
+    fn starts(&self) -> &Vec<(String, uint)>;
+
+    fn table(&self) -> LRTable<'a>;
+}
+
+#[deriving(Show)]
+pub struct SLRState<'a> {
+    id : uint,
+    items : SortedSet<Item<'a>>,
+    moves : HashMap<Symbol, uint>
+}
+
+impl<'a> SLRState<'a> {
+    pub fn id(&self) -> uint {
+        self.id
+    }
+
+    pub fn items(&self) -> &SortedSet<Item<'a>> {
+        &self.items
+    }
+
+    pub fn moves(&self) -> &HashMap<Symbol, uint> {
+        &self.moves
+    }
+}
+
+pub struct SLRAutomaton<'a> {
+    grammar : &'a Grammar,
+    states : Vec<SLRState<'a>>,
+    starts : Vec<(String, uint)>
+}
+
+#[deriving(Show, Clone, PartialEq)]
+pub enum LRAction<'a> {
+    Goto(uint),
+    Shift(uint),
     Reduce(&'a Production),
     Accept,
     Error
 }
 
 #[deriving(Show)]
-pub struct SLRTable<'a> {
+pub struct LR1State<'a> {
+    id : uint,
+    items : SortedSet<LR1Item<'a>>,
+    moves : HashMap<Symbol, uint>
+}
+
+pub struct LR1Automaton<'a> {
+    grammar : &'a Grammar,
+    states : Vec<LR1State<'a>>,
+    starts : Vec<(String, uint)>
+}
+
+impl<'a> LR1Automaton<'a> {
+    /// The LR(0) items of each state with their moves. Several states may
+    /// share the same LR(0) items and differ only in their lookaheads.
+    fn cores(&self) -> Vec<(SortedSet<Item<'a>>, HashMap<Symbol, uint>)> {
+        self.states.iter().map(|state| {
+            let mut items : SortedSet<Item<'a>> = SortedSet::new();
+            for item in state.items.iter() {
+                items.add(item.item.clone());
+            }
+            (items, state.moves.clone())
+        }).collect()
+    }
+
+}
+
+impl<'a> Automaton<'a> for LR1Automaton<'a> {
+    fn grammar(&self) -> &'a Grammar {
+        self.grammar
+    }
+
+    fn len(&self) -> uint {
+        self.states.len()
+    }
+
+    fn starts(&self) -> &Vec<(String, uint)> {
+        &self.starts
+    }
+
+    /// The LR(1) table: a complete item reduces on its own lookahead. The
+    /// complete augmented start `S' -> S $ .` is never reduced by, the parse
+    /// is accepted on the `$` before it.
+    fn table(&self) -> LRTable<'a> {
+        let mut reductions : Vec<Vec<(Item<'a>, SortedSet<Symbol>)>> = Vec::new();
+        for state in self.states.iter() {
+            let mut reduce = Vec::new();
+            for item in state.items.iter() {
+                if item.item.dot == item.item.production.symbols.len() && !self.grammar.augmented_starts.contains(&item.item.production.nt) {
+                    reduce.push((item.item.clone(), SortedSet::singleton(item.lookahead.clone())));
+                }
+            }
+            reductions.push(reduce);
+        }
+        LRTable::build(self.grammar, self.starts.clone(), self.cores(), reductions)
+    }
+}
+
+pub type SLRTable<'a> = LRTable<'a>;
+
+/// Two or more actions competing for the same state and lookahead. The items
+/// are the ones in the state which gave rise to the competing actions.
+pub struct Conflict<'a> {
+    pub state : uint,
+    pub symbol : Symbol,
+    pub actions : Vec<LRAction<'a>>,
+    pub items : Vec<Item<'a>>
+}
+
+/// A shift/reduce conflict settled by the precedence and associativity
+/// declarations. `reason` names the rule which chose the action.
+pub struct Resolution<'a> {
+    pub state : uint,
+    pub symbol : Symbol,
+    pub shift : uint,
+    pub production : &'a Production,
+    pub chosen : LRAction<'a>,
+    pub reason : String
+}
+
+pub struct LRTable<'a> {
     actions : HashMap<(uint,Symbol),LRAction<'a>>,
+    sources : HashMap<(uint,Symbol),Vec<Item<'a>>>,
+    conflicts : Vec<Conflict<'a>>,
+    resolutions : Vec<Resolution<'a>>,
+    starts : Vec<(String, uint)>,
+    states : uint
 }
 
-impl<'a> SLRAutomaton<'a> {
-    pub fn table<'a>(&'a self) -> SLRTable<'a> {
-        let mut table = SLRTable{
-            actions : HashMap::new()
+impl<'a> LRTable<'a> {
+    fn new(starts : Vec<(String, uint)>, states : uint) -> LRTable<'a> {
+        LRTable{
+            starts : starts,
+            states : states,
+            actions : HashMap::new(),
+            sources : HashMap::new(),
+            conflicts : Vec::new(),
+            resolutions : Vec::new()
+        }
+    }
+
+    pub fn action(&self, state : uint, sym : &Symbol) -> Option<&LRAction<'a>> {
+        self.actions.find(&(state, sym.clone()))
+    }
+
+    /// The number of states, which are numbered from 0.
+    pub fn len(&self) -> uint {
+        self.states
+    }
+
+    /// The start symbols with their initial states.
+    pub fn starts(&self) -> &Vec<(String, uint)> {
+        &self.starts
+    }
+
+    pub fn start_state(&self, start : &str) -> Option<uint> {
+        self.starts.iter().find(|&&(ref name, _)| name.as_slice() == start).map(|&(_, state)| state)
+    }
+
+    pub fn conflicts(&self) -> &Vec<Conflict<'a>> {
+        &self.conflicts
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        self.conflicts.len() > 0
+    }
+
+    pub fn resolutions(&self) -> &Vec<Resolution<'a>> {
+        &self.resolutions
+    }
+
+    /// The terminals which have an action other than `Error` in a state.
+    pub fn expected(&self, state : uint) -> Vec<Symbol> {
+        let mut expected : Vec<Symbol> = Vec::new();
+        for (&(s, ref sym), action) in self.actions.iter() {
+            if s != state {
+                continue
+            }
+            match (sym, action) {
+                (&NonTerm(_), _) | (&EmptyString, _) | (_, &Error) | (_, &Goto(_)) => {}
+                _ => { expected.push(sym.clone()) }
+            }
+        }
+        expected.sort();
+        expected
+    }
+
+    /// Settles a shift/reduce conflict the way yacc does when both the
+    /// lookahead and the production have a precedence: the higher one wins
+    /// and on a tie the lookahead's associativity decides.
+    fn resolve(grammar : &Grammar, state : uint, sym : &Symbol, existing : &LRAction<'a>, action : &LRAction<'a>) -> Option<Resolution<'a>> {
+        let (shift, production) = match (existing, action) {
+            (&Shift(s), &Reduce(p)) | (&Reduce(p), &Shift(s)) => { (s, p) }
+            _ => { return None }
         };
-        for (i,state) in self.states.iter().enumerate() {
-            for (sym, target) in state.moves.iter() {
+        let name = match *sym {
+            Term(ref name) => { name }
+            _ => { return None }
+        };
+        let (token_level, assoc) = match grammar.precedence(name) {
+            Some(prec) => { prec }
+            None => { return None }
+        };
+        let (rule_term, rule_level) = match grammar.production_precedence(production) {
+            Some(prec) => { prec }
+            None => { return None }
+        };
+        let (chosen, reason) = if rule_level > token_level {
+            (Reduce(production), format!("{} has higher precedence than {}", rule_term, name))
+        } else if rule_level < token_level {
+            (Shift(shift), format!("{} has higher precedence than {}", name, rule_term))
+        } else {
+            match assoc {
+                Left => { (Reduce(production), format!("%left {}", name)) }
+                Right => { (Shift(shift), format!("%right {}", name)) }
+                NonAssoc => { (Error, format!("%nonassoc {}", name)) }
+            }
+        };
+        Some(Resolution{
+            state: state,
+            symbol: sym.clone(),
+            shift: shift,
+            production: production,
+            chosen: chosen,
+            reason: reason
+        })
+    }
+
+    /// Fills in the shift, goto and accept actions from the LR(0) items and
+    /// moves of each state and the reduce actions from `reductions`, which
+    /// holds for each state the complete items and the lookaheads they
+    /// reduce on.
+    fn build(grammar : &Grammar, starts : Vec<(String, uint)>, states : Vec<(SortedSet<Item<'a>>, HashMap<Symbol, uint>)>, reductions : Vec<Vec<(Item<'a>, SortedSet<Symbol>)>>) -> LRTable<'a> {
+        let mut table = LRTable::new(starts, states.len());
+        for (i, (items, moves)) in states.into_iter().enumerate() {
+            for (sym, target) in moves.iter() {
+                let sources = items.iter().filter(|item| {
+                    item.dot < item.production.symbols.len() && &item.production.symbols[item.dot] == sym
+                }).map(|item| item.clone()).collect::<Vec<Item<'a>>>();
                 match sym {
                     &EmptyString => {
                     }
+                    &EndOfInput => {
+                        table.add(grammar, i, sym, Accept, sources);
+                    }
                     &Term(_) => {
-                        table.actions.insert((i,sym.clone()), Shift(*target));
+                        table.add(grammar, i, sym, Shift(*target), sources);
                     }
                     &NonTerm(_) => {
-                        table.actions.insert((i,sym.clone()), Goto(*target));
+                        table.add(grammar, i, sym, Goto(*target), sources);
                     }
                 };
             }
+        }
+        for (i, reduce) in reductions.into_iter().enumerate() {
+            for (item, lookaheads) in reduce.into_iter() {
+                for sym in lookaheads.iter() {
+                    table.add(grammar, i, sym, Reduce(item.production), vec![item.clone()]);
+                }
+            }
+        }
+        return table;
+    }
+
+    fn add(&mut self, grammar : &Grammar, state : uint, sym : &Symbol, action : LRAction<'a>, items : Vec<Item<'a>>) {
+        let key = (state, sym.clone());
+        let existing = match self.actions.find(&key) {
+            Some(a) => { a.clone() }
+            None => {
+                self.actions.insert(key.clone(), action);
+                self.sources.insert(key, items);
+                return
+            }
+        };
+        if existing == action {
+            return
+        }
+        // A %nonassoc resolution leaves Error in the table. It settles the
+        // shift/reduce conflict for good, only a reduce by another production
+        // is still a conflict, with the production the resolution was about.
+        let existing = match existing {
+            Error => {
+                let resolved = self.resolutions.iter().find(|r| r.state == state && &r.symbol == sym).map(|r| r.production);
+                match (resolved, &action) {
+                    (Some(production), &Reduce(p)) if p != production => { Reduce(production) }
+                    (Some(_), _) => { return }
+                    (None, _) => { existing }
+                }
+            }
+            _ => { existing }
+        };
+        let in_conflict = self.conflicts.iter().any(|c| c.state == state && &c.symbol == sym);
+        if !in_conflict {
+            match LRTable::resolve(grammar, state, sym, &existing, &action) {
+                Some(resolution) => {
+                    // Error keeps the reduce's items, for a later reduce/reduce
+                    // conflict on the same symbol.
+                    let keep = match (&resolution.chosen, &action) {
+                        (&Error, &Reduce(_)) => { true }
+                        (chosen, action) => { chosen == action }
+                    };
+                    if keep {
+                        self.sources.insert(key.clone(), items);
+                    }
+                    self.actions.insert(key, resolution.chosen.clone());
+                    self.resolutions.push(resolution);
+                    return
+                }
+                None => {}
+            }
+        }
+        for conflict in self.conflicts.iter_mut() {
+            if conflict.state == state && &conflict.symbol == sym {
+                if !conflict.actions.contains(&action) {
+                    conflict.actions.push(action);
+                }
+                for item in items.into_iter() {
+                    if !conflict.items.contains(&item) {
+                        conflict.items.push(item);
+                    }
+                }
+                return
+            }
+        }
+        let mut conflict_items = self.sources[key].clone();
+        for item in items.into_iter() {
+            if !conflict_items.contains(&item) {
+                conflict_items.push(item);
+            }
+        }
+        self.conflicts.push(Conflict{
+            state: state,
+            symbol: sym.clone(),
+            actions: vec![existing, action],
+            items: conflict_items
+        });
+    }
+}
+
+impl<'a> SLRAutomaton<'a> {
+    pub fn states(&self) -> &Vec<SLRState<'a>> {
+        &self.states
+    }
+
+    fn cores(&self) -> Vec<(SortedSet<Item<'a>>, HashMap<Symbol, uint>)> {
+        self.states.iter().map(|state| (state.items.clone(), state.moves.clone())).collect()
+    }
+
+    /// The LALR(1) table: a complete item reduces on the lookaheads computed
+    /// for it by `lalr_lookaheads`.
+    pub fn lalr_table<'a>(&'a self) -> LRTable<'a> {
+        let lookaheads = self.lalr_lookaheads();
+        let mut reductions : Vec<Vec<(Item<'a>, SortedSet<Symbol>)>> = Vec::new();
+        for (i, state) in self.states.iter().enumerate() {
+            let mut reduce = Vec::new();
             for item in state.items.iter() {
                 if item.dot == item.production.symbols.len() {
-                    for sym in self.grammar.FOLLOW(NonTerm(item.production.nt.clone())).iter() {
-                        table.actions.insert((i,sym.clone()), Reduce(item.production));
+                    let la = lookaheads.find(&(i, item.clone())).map(|s| s.clone()).unwrap_or(SortedSet::new());
+                    reduce.push((item.clone(), la));
+                }
+            }
+            reductions.push(reduce);
+        }
+        LRTable::build(self.grammar, self.starts.clone(), self.cores(), reductions)
+    }
+
+    /// Computes the LALR(1) lookaheads of every item in every state by
+    /// propagation. An item `A -> a . B b` with lookaheads L gives the items
+    /// `B -> . g` of the same state FIRST(b), plus L when `b` is nullable
+    /// (spontaneous generation), and the item `A -> a B . b` in the state
+    /// reached on `B` gets L (propagation). This is repeated until no
+    /// lookahead set grows.
+    pub fn lalr_lookaheads<'a>(&'a self) -> TreeMap<(uint, Item<'a>), SortedSet<Symbol>> {
+        let analysis = self.grammar.analysis();
+        let mut lookaheads : TreeMap<(uint, Item<'a>), SortedSet<Symbol>> = TreeMap::new();
+        for (i, state) in self.states.iter().enumerate() {
+            for item in state.items.iter() {
+                lookaheads.insert((i, item.clone()), SortedSet::new());
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, state) in self.states.iter().enumerate() {
+                for item in state.items.iter() {
+                    if item.dot >= item.production.symbols.len() {
+                        continue
+                    }
+                    let la = lookaheads[(i, item.clone())].clone();
+                    let sym = &item.production.symbols[item.dot];
+                    match *sym {
+                        NonTerm(ref name) => {
+                            let rest = item.production.symbols.slice_from(item.dot + 1);
+                            let mut generated = analysis.first_vec(rest).minus(&SortedSet::singleton(EmptyString));
+                            if analysis.nullable_vec(rest) {
+                                generated.addall(la.clone());
+                            }
+                            for other in state.items.iter() {
+                                if other.dot == 0 && &other.production.nt == name {
+                                    changed = add_lookaheads(&mut lookaheads, (i, other.clone()), &generated) || changed;
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    match state.moves.find(sym) {
+                        Some(target) => {
+                            let next = Item{production: item.production, dot: item.dot + 1};
+                            changed = add_lookaheads(&mut lookaheads, (*target, next), &la) || changed;
+                        }
+                        None => {}
                     }
                 }
             }
         }
-        return table;
+        lookaheads
+    }
+}
+
+impl<'a> Automaton<'a> for SLRAutomaton<'a> {
+    fn grammar(&self) -> &'a Grammar {
+        self.grammar
+    }
+
+    fn len(&self) -> uint {
+        self.states.len()
+    }
+
+    fn starts(&self) -> &Vec<(String, uint)> {
+        &self.starts
+    }
+
+    /// The SLR(1) table: a complete item reduces on the FOLLOW set of its
+    /// nonterminal.
+    fn table(&self) -> LRTable<'a> {
+        let analysis = self.grammar.analysis();
+        let mut reductions : Vec<Vec<(Item<'a>, SortedSet<Symbol>)>> = Vec::new();
+        for state in self.states.iter() {
+            let mut reduce = Vec::new();
+            for item in state.items.iter() {
+                if item.dot == item.production.symbols.len() {
+                    reduce.push((item.clone(), analysis.follow(&item.production.nt)));
+                }
+            }
+            reductions.push(reduce);
+        }
+        LRTable::build(self.grammar, self.starts.clone(), self.cores(), reductions)
+    }
+}
+
+fn add_lookaheads<'a>(lookaheads : &mut TreeMap<(uint, Item<'a>), SortedSet<Symbol>>, key : (uint, Item<'a>), symbols : &SortedSet<Symbol>) -> bool {
+    let mut set = lookaheads.pop(&key).unwrap_or(SortedSet::new());
+    let before = set.len();
+    set.addall(symbols.clone());
+    let grew = set.len() != before;
+    lookaheads.insert(key, set);
+    grew
+}
+
+impl<'a> Show for LRTable<'a> {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        let mut keys : Vec<&(uint,Symbol)> = self.actions.keys().collect();
+        keys.sort();
+        fmtr.write_str("Parse Table\n").ok();
+        for key in keys.into_iter() {
+            let &(state, ref sym) = key;
+            fmtr.write_str(format!("  {} {} : {}\n", state, sym, self.actions[*key]).as_slice()).ok();
+        }
+        return Ok(());
+    }
+}
+
+impl<'a> Show for Resolution<'a> {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        fmtr.write_str(format!("shift/reduce conflict in state {} on {} between {} and {} resolved as {} by {}",
+            self.state, self.symbol, Shift(self.shift), Reduce(self.production), self.chosen, self.reason).as_slice()).ok();
+        return Ok(());
+    }
+}
+
+impl<'a> Show for Conflict<'a> {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        let kind = if self.actions.iter().any(|a| match *a { Shift(_) => true, _ => false }) {
+            "shift/reduce"
+        } else {
+            "reduce/reduce"
+        };
+        fmtr.write_str(format!("{} conflict in state {} on {}\n", kind, self.state, self.symbol).as_slice()).ok();
+        for action in self.actions.iter() {
+            fmtr.write_str(format!("    {}\n", action).as_slice()).ok();
+        }
+        for item in self.items.iter() {
+            fmtr.write_str(format!("      {}\n", item).as_slice()).ok();
+        }
+        return Ok(());
+    }
+}
+
+impl<'a> Show for LR1Automaton<'a> {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        fmtr.write_str("LR(1) Automaton\n").ok();
+        fmtr.write_str(format!("  symbols : {}\n", self.grammar.symbols).as_slice()).ok();
+        fmtr.write_str(format!("  starts : {}\n", self.starts).as_slice()).ok();
+        for (i,state) in self.states.iter().enumerate() {
+            fmtr.write_str(format!("    state : {}\n", i).as_slice()).ok();
+            for item in state.items.iter() {
+                fmtr.write_str(format!("      {}\n", item).as_slice()).ok();
+            }
+            fmtr.write_str("      moves : \n").ok();
+            for (k,v) in state.moves.iter() {
+                fmtr.write_str(format!("        {} -> {}\n", k, v).as_slice()).ok();
+            }
+        }
+        return Ok(());
     }
 }
 
@@ -553,6 +2229,7 @@ impl<'a> Show for SLRAutomaton<'a> {
     fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
         fmtr.write_str("LR(0) Automaton\n").ok();
         fmtr.write_str(format!("  symbols : {}\n", self.grammar.symbols).as_slice()).ok();
+        fmtr.write_str(format!("  starts : {}\n", self.starts).as_slice()).ok();
         for (i,state) in self.states.iter().enumerate() {
             fmtr.write_str(format!("    state : {}\n", i).as_slice()).ok();
             for item in state.items.iter() {
@@ -567,3 +2244,475 @@ impl<'a> Show for SLRAutomaton<'a> {
     }
 }
 
+/// A terminal of the input to a `Parser`: the terminal the table is indexed
+/// by and the text it was matched from.
+#[deriving(Show, Clone)]
+pub struct InputToken {
+    pub terminal : Symbol,
+    pub lexeme : String
+}
+
+#[deriving(Show)]
+pub enum ParseTree<'a> {
+    Leaf(InputToken),
+    Branch(&'a Production, Vec<ParseTree<'a>>)
+}
+
+impl<'a> ParseTree<'a> {
+    /// Converts the tree into a concrete syntax tree of `Node`s. A reduction
+    /// is labeled with its nonterminal and has a kid for each symbol of the
+    /// production's body. A terminal is labeled with its name and has its
+    /// lexeme as its only kid.
+    pub fn to_node(&self) -> Node {
+        match *self {
+            Leaf(ref token) => {
+                Node::new(Grammar::name(token.terminal.clone()).as_slice())
+                    .addkid(Node::new(token.lexeme.as_slice()))
+            }
+            Branch(production, ref kids) => {
+                let mut node = Node::new(production.nt.as_slice());
+                for kid in kids.iter() {
+                    node = node.addkid(kid.to_node());
+                }
+                node
+            }
+        }
+    }
+}
+
+/// The parser reached a state with no action for the next terminal.
+/// `position` is the index of the offending token in the input.
+pub struct SyntaxError {
+    pub state : uint,
+    pub position : uint,
+    pub got : Symbol,
+    pub expected : Vec<Symbol>
+}
+
+impl Show for SyntaxError {
+    fn fmt(&self, fmtr : &mut Formatter) -> Result<(), FormatError> {
+        let expected : Vec<String> = self.expected.iter().map(|sym| Grammar::name(sym.clone())).collect();
+        fmtr.write_str(format!("syntax error at token {} (state {}): expected one of {} but got {}",
+            self.position, self.state, expected.connect(", "), Grammar::name(self.got.clone())).as_slice()).ok();
+        return Ok(());
+    }
+}
+
+/// A table driven LR parser. It runs the usual shift/reduce/goto loop over
+/// any `LRTable` built by this module, starting in the initial state of one
+/// of the grammar's start symbols.
+pub struct Parser<'a, 'b> {
+    table : &'b LRTable<'a>,
+    initial : uint
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    /// A parser for the first start symbol.
+    pub fn new(table : &'b LRTable<'a>) -> Parser<'a, 'b> {
+        Parser{table: table, initial: table.starts[0].val1()}
+    }
+
+    /// A parser for the given start symbol, if it is one.
+    pub fn with_start(table : &'b LRTable<'a>, start : &str) -> Option<Parser<'a, 'b>> {
+        table.start_state(start).map(|state| Parser{table: table, initial: state})
+    }
+
+    pub fn parse<I: Iterator<InputToken>>(&self, mut tokens : I) -> Result<ParseTree<'a>, SyntaxError> {
+        let end = InputToken{terminal: EndOfInput, lexeme: "".to_string()};
+        let mut states : Vec<uint> = vec![self.initial];
+        let mut trees : Vec<ParseTree<'a>> = Vec::new();
+        let mut position : uint = 0;
+        let mut token = tokens.next().unwrap_or(end.clone());
+        loop {
+            let state = *states.last().unwrap();
+            let action = self.table.action(state, &token.terminal).map(|a| a.clone());
+            match action {
+                Some(Shift(next)) => {
+                    states.push(next);
+                    trees.push(Leaf(token));
+                    token = tokens.next().unwrap_or(end.clone());
+                    position += 1;
+                }
+                Some(Reduce(production)) => {
+                    let mut kids : Vec<ParseTree<'a>> = Vec::new();
+                    for _ in range(0, production.symbols.len()) {
+                        states.pop();
+                        kids.push(trees.pop().unwrap());
+                    }
+                    kids.reverse();
+                    let top = *states.last().unwrap();
+                    match self.table.action(top, &NonTerm(production.nt.clone())) {
+                        Some(&Goto(next)) => { states.push(next); }
+                        _ => { fail!(format!("no goto on {} from state {}", production.nt, top)) }
+                    }
+                    trees.push(Branch(production, kids));
+                }
+                Some(Accept) => {
+                    return Ok(trees.pop().unwrap())
+                }
+                Some(Goto(_)) | Some(Error) | None => {
+                    return Err(SyntaxError{
+                        state: state,
+                        position: position,
+                        got: token.terminal,
+                        expected: self.table.expected(state)
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{Grammar, Automaton, Symbol, SortedSet, Term, NonTerm, EmptyString, EndOfInput, Reduce, Error};
+    use super::{LRTable, Parser, InputToken, GrammarBuilder, nt, t};
+    use super::{MisplacedSymbol, Left, NoStartSymbol, UndefinedNonTerm, UnreachableNonTerm, UnproductiveNonTerm, UnusedTerm};
+    use gram_lexer::Position;
+
+    /// Loads `text`, failing the test if it is not a grammar. Shared with
+    /// the tests of the other modules.
+    pub fn grammar(text : &str) -> Grammar {
+        match Grammar::from_str(text) {
+            Ok(grammar) => { grammar }
+            Err(err) => { fail!("{}", err) }
+        }
+    }
+
+    fn set(symbols : &[Symbol]) -> SortedSet<Symbol> {
+        let mut set = SortedSet::new();
+        for sym in symbols.iter() {
+            set.add(sym.clone());
+        }
+        set
+    }
+
+    static OPTIONAL : &'static str = "
+        Start -> Head Tail END ;
+        Head -> HEAD | %empty ;
+        Tail -> TAIL Tail | %empty ;
+    ";
+
+    #[test]
+    fn nullable_and_first() {
+        let g = grammar(OPTIONAL);
+        let analysis = g.analysis();
+        assert!(analysis.nullable(&NonTerm("Head".to_string())));
+        assert!(analysis.nullable(&NonTerm("Tail".to_string())));
+        assert!(!analysis.nullable(&NonTerm("Start".to_string())));
+        assert_eq!(analysis.first(&NonTerm("Head".to_string())), set(&[Term("HEAD".to_string()), EmptyString]));
+        assert_eq!(analysis.first(&NonTerm("Start".to_string())),
+                   set(&[Term("END".to_string()), Term("HEAD".to_string()), Term("TAIL".to_string())]));
+    }
+
+    fn at(offset : uint, line : uint, column : uint) -> Option<Position> {
+        Some(Position{offset: offset, line: line, column: column})
+    }
+
+    #[test]
+    fn warnings_do_not_stop_loading() {
+        let g = grammar("%token UNUSED\nS -> A ;\nA -> X ;\nDead -> X ;\n");
+        assert_eq!(g.validate(), vec![
+            UnreachableNonTerm("Dead".to_string(), at(32, 4, 1)),
+            UnusedTerm("UNUSED".to_string(), at(7, 1, 8))
+        ]);
+    }
+
+    #[test]
+    fn errors_stop_loading() {
+        match Grammar::from_str("S -> A B | Missing ;\nA -> X ;\nB -> B Y ;\n") {
+            Err(::Invalid(diagnostics)) => {
+                assert_eq!(diagnostics, vec![
+                    UndefinedNonTerm("Missing".to_string(), at(11, 1, 12)),
+                    UnproductiveNonTerm("B".to_string(), at(30, 3, 1)),
+                    UnproductiveNonTerm("S".to_string(), at(0, 1, 1))
+                ]);
+            }
+            _ => { fail!("the grammar was not reported as invalid") }
+        }
+    }
+
+    #[test]
+    fn no_productions_has_no_start_symbol() {
+        match GrammarBuilder::new().build() {
+            Err(::Invalid(diagnostics)) => { assert_eq!(diagnostics, vec![NoStartSymbol]); }
+            _ => { fail!("an empty grammar was not reported as invalid") }
+        }
+    }
+
+    /// The bodies of the productions of `name`, sorted.
+    fn bodies(g : &Grammar, name : &str) -> Vec<Vec<Symbol>> {
+        let mut bodies : Vec<Vec<Symbol>> = g.productions().iter().filter(|p| p.nt().as_slice() == name).map(|p| p.symbols().clone()).collect();
+        bodies.sort();
+        bodies
+    }
+
+    fn sorted(mut bodies : Vec<Vec<Symbol>>) -> Vec<Vec<Symbol>> {
+        bodies.sort();
+        bodies
+    }
+
+    #[test]
+    fn ebnf_desugars_to_productions() {
+        let g = grammar("S -> XX? YY* ZZ+ (AA | BB)* ;");
+        assert_eq!(bodies(&g, "S"), vec![vec![nt("XX?"), nt("YY*"), nt("ZZ+"), nt("(AA | BB)*")]]);
+        assert_eq!(bodies(&g, "XX?"), sorted(vec![vec![t("XX")], vec![]]));
+        assert_eq!(bodies(&g, "YY*"), sorted(vec![vec![nt("YY*"), t("YY")], vec![]]));
+        assert_eq!(bodies(&g, "ZZ+"), sorted(vec![vec![nt("ZZ+"), t("ZZ")], vec![t("ZZ")]]));
+        assert_eq!(bodies(&g, "(AA | BB)*"),
+                   sorted(vec![vec![nt("(AA | BB)*"), t("AA")], vec![nt("(AA | BB)*"), t("BB")], vec![]]));
+    }
+
+    #[test]
+    fn ebnf_reuses_the_same_item() {
+        let g = grammar("S -> XX* | YY XX* ;");
+        assert_eq!(bodies(&g, "XX*").len(), 2);
+        assert_eq!(g.nonterminals(), vec!["S".to_string(), "XX*".to_string()]);
+    }
+
+    #[test]
+    fn ebnf_groups_keep_their_actions() {
+        let g = grammar("S -> (AA {one}) (AA {two}) ;");
+        let actions : Vec<(String, Option<String>)> = g.productions().iter().filter(|p| p.nt().as_slice() != "S").map(|p| {
+            (p.nt().clone(), p.action().map(|a| a.clone()))
+        }).collect();
+        assert_eq!(actions, vec![
+            ("(AA {one})".to_string(), Some("one".to_string())),
+            ("(AA {two})".to_string(), Some("two".to_string()))
+        ]);
+    }
+
+    #[test]
+    fn builder_matches_the_grammar_file() {
+        let built = match GrammarBuilder::new()
+                .left(&["PLUS"])
+                .rule("Expr", &[nt("Expr"), t("PLUS"), nt("Expr")])
+                .rule("Expr", &[t("NUMBER")])
+                .build() {
+            Ok(grammar) => { grammar }
+            Err(err) => { fail!("{}", err) }
+        };
+        let read = grammar("%left PLUS\nExpr -> Expr PLUS Expr | NUMBER ;");
+        assert_eq!(built.starts(), read.starts());
+        assert_eq!(built.terminals(), read.terminals());
+        assert_eq!(built.nonterminals(), read.nonterminals());
+        for name in read.nonterminals().iter() {
+            assert_eq!(bodies(&built, name.as_slice()), bodies(&read, name.as_slice()));
+        }
+        assert_eq!(built.precedence(&"PLUS".to_string()), Some((1, Left)));
+        assert_eq!(built.precedence(&"PLUS".to_string()), read.precedence(&"PLUS".to_string()));
+
+        let built_automaton = built.lr0_automaton();
+        let read_automaton = read.lr0_automaton();
+        let built_table = built_automaton.lalr_table();
+        let read_table = read_automaton.lalr_table();
+        assert_eq!(built_table.len(), read_table.len());
+        assert!(!built_table.has_conflicts());
+        assert_eq!(built_table.resolutions().len(), read_table.resolutions().len());
+        let input = ["NUMBER", "PLUS", "NUMBER", "PLUS", "NUMBER"];
+        assert_eq!(parse(&built_table, &input), parse(&read_table, &input));
+    }
+
+    /// The tree `table` parses `input` into, printed.
+    fn parse(table : &LRTable, input : &[&str]) -> String {
+        match Parser::new(table).parse(tokens(input).into_iter()) {
+            Ok(tree) => { format!("{}", tree.to_node()) }
+            Err(err) => { fail!("{}", err) }
+        }
+    }
+
+    #[test]
+    fn builder_rejects_misplaced_symbols() {
+        match GrammarBuilder::new().rule("S", &[t("AA"), EndOfInput]).build() {
+            Err(::Invalid(diagnostics)) => { assert_eq!(diagnostics, vec![MisplacedSymbol("S".to_string(), EndOfInput)]); }
+            _ => { fail!("$ in a body was accepted") }
+        }
+        match GrammarBuilder::new().rule("S", &[t("AA"), EmptyString]).build() {
+            Err(::Invalid(diagnostics)) => { assert_eq!(diagnostics, vec![MisplacedSymbol("S".to_string(), EmptyString)]); }
+            _ => { fail!("%empty next to another symbol was accepted") }
+        }
+        match GrammarBuilder::new().rule("S", &[t("AA"), nt("S")]).rule("S", &[EmptyString]).build() {
+            Ok(g) => { assert_eq!(bodies(&g, "S"), sorted(vec![vec![t("AA"), nt("S")], vec![]])); }
+            Err(err) => { fail!("{}", err) }
+        }
+    }
+
+    #[test]
+    fn literal_names_avoid_written_terminals() {
+        let g = grammar("S -> \"+\" PLUS \"+=\" \"+\" ;");
+        assert_eq!(g.literals().find(&"+".to_string()), Some(&"PLUS_2".to_string()));
+        assert_eq!(g.literals().find(&"+=".to_string()), Some(&"PLUS_EQ".to_string()));
+        assert_eq!(bodies(&g, "S"), vec![vec![t("PLUS_2"), t("PLUS"), t("PLUS_EQ"), t("PLUS_2")]]);
+    }
+
+    #[test]
+    fn empty_only_starts_an_alternative() {
+        assert!(Grammar::from_str("A -> %empty {} | B ;\nB -> X ;").is_ok());
+        match Grammar::from_str("A -> B %empty ;\nB -> X ;") {
+            Err(::Parse(_)) => {}
+            _ => { fail!("%empty after a symbol was accepted") }
+        }
+    }
+
+    #[test]
+    fn follow_reaches_past_nullable_symbols() {
+        let g = grammar(OPTIONAL);
+        let analysis = g.analysis();
+        assert_eq!(analysis.follow(&"Start".to_string()), set(&[EndOfInput]));
+        assert_eq!(analysis.follow(&"Head".to_string()), set(&[Term("END".to_string()), Term("TAIL".to_string())]));
+        assert_eq!(analysis.follow(&"Tail".to_string()), set(&[Term("END".to_string())]));
+    }
+
+    /// The grammar of assignments through pointers from the dragon book,
+    /// which is LALR(1) but not SLR(1).
+    static ASSIGN : &'static str = "
+        S -> Lhs EQ Rhs | Rhs ;
+        Lhs -> STAR Rhs | ID ;
+        Rhs -> Lhs ;
+    ";
+
+    #[test]
+    fn lalr_but_not_slr() {
+        let g = grammar(ASSIGN);
+        let automaton = g.lr0_automaton();
+        let slr = automaton.table();
+        assert_eq!(slr.conflicts().len(), 1);
+        assert_eq!(slr.conflicts()[0].symbol, Term("EQ".to_string()));
+        let lalr = automaton.lalr_table();
+        assert!(!lalr.has_conflicts());
+        assert_eq!(lalr.len(), slr.len());
+    }
+
+    /// LR(1) but not LALR(1): merging the two states reached on `CC` gives a
+    /// reduce/reduce conflict.
+    static SPLIT : &'static str = "
+        S -> AA X DD | BB Y DD | AA Y EE | BB X EE ;
+        X -> CC ;
+        Y -> CC ;
+    ";
+
+    #[test]
+    fn lr1_but_not_lalr() {
+        let g = grammar(SPLIT);
+        let lr0 = g.lr0_automaton();
+        let lalr = lr0.lalr_table();
+        assert!(lalr.has_conflicts());
+        for conflict in lalr.conflicts().iter() {
+            assert!(conflict.actions.iter().all(|action| match *action { Reduce(_) => { true } _ => { false } }));
+        }
+        let lr1 = g.lr1_automaton();
+        assert!(!lr1.table().has_conflicts());
+        assert_eq!(lr1.len(), lr0.len() + 1);
+    }
+
+    pub static EXPR : &'static str = "
+        Expr -> Expr PLUS Term | Term ;
+        Term -> Term STAR Factor | Factor ;
+        Factor -> NUMBER | LPAREN Expr RPAREN ;
+    ";
+
+    #[test]
+    fn pager_splits_only_where_lalr_conflicts() {
+        let g = grammar(SPLIT);
+        let lr0 = g.lr0_automaton();
+        let pager = g.minimal_lr1_automaton();
+        assert!(!pager.table().has_conflicts());
+        assert_eq!(pager.len(), lr0.len() + 1);
+    }
+
+    #[test]
+    fn pager_merges_compatible_states() {
+        let g = grammar(EXPR);
+        let lr1 = g.lr1_automaton();
+        let pager = g.minimal_lr1_automaton();
+        assert!(!pager.table().has_conflicts());
+        assert_eq!(pager.len(), g.lr0_automaton().len());
+        assert!(pager.len() < lr1.len());
+    }
+
+    fn tokens(terminals : &[&str]) -> Vec<InputToken> {
+        terminals.iter().map(|name| InputToken{terminal: Term(name.to_string()), lexeme: name.to_string()}).collect()
+    }
+
+    #[test]
+    fn parser_builds_the_tree() {
+        let g = grammar(EXPR);
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        let tree = match Parser::new(&table).parse(tokens(&["NUMBER", "PLUS", "NUMBER", "STAR", "NUMBER"]).into_iter()) {
+            Ok(tree) => { tree }
+            Err(err) => { fail!("{}", err) }
+        };
+        let node = tree.to_node();
+        assert_eq!(node.label.as_slice(), "Expr");
+        let labels : Vec<&str> = node.kids.iter().map(|kid| kid.label.as_slice()).collect();
+        assert_eq!(labels, vec!["Expr", "PLUS", "Term"]);
+        let labels : Vec<&str> = node.kids[2].kids.iter().map(|kid| kid.label.as_slice()).collect();
+        assert_eq!(labels, vec!["Term", "STAR", "Factor"]);
+    }
+
+    #[test]
+    fn parser_reports_where_it_stopped() {
+        let g = grammar(EXPR);
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        match Parser::new(&table).parse(tokens(&["NUMBER", "PLUS", "RPAREN"]).into_iter()) {
+            Ok(tree) => { fail!("parsed {}", tree.to_node()) }
+            Err(err) => {
+                assert_eq!(err.position, 2);
+                assert_eq!(err.got, Term("RPAREN".to_string()));
+                assert_eq!(err.expected, vec![Term("LPAREN".to_string()), Term("NUMBER".to_string())]);
+            }
+        }
+    }
+
+    #[test]
+    fn left_associates_by_reducing() {
+        let g = grammar("%left DASH\n%left STAR\nE -> E DASH E | E STAR E | NUMBER ;");
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        assert!(!table.has_conflicts());
+        assert_eq!(table.resolutions().len(), 4);
+        let tree = match Parser::new(&table).parse(tokens(&["NUMBER", "DASH", "NUMBER", "STAR", "NUMBER", "DASH", "NUMBER"]).into_iter()) {
+            Ok(tree) => { tree }
+            Err(err) => { fail!("{}", err) }
+        };
+        // ((N - (N * N)) - N)
+        let node = tree.to_node();
+        let labels : Vec<&str> = node.kids[0].kids.iter().map(|kid| kid.label.as_slice()).collect();
+        assert_eq!(labels, vec!["E", "DASH", "E"]);
+        let labels : Vec<&str> = node.kids[0].kids[2].kids.iter().map(|kid| kid.label.as_slice()).collect();
+        assert_eq!(labels, vec!["E", "STAR", "E"]);
+        assert_eq!(node.kids[2].kids[0].label.as_slice(), "NUMBER");
+    }
+
+    #[test]
+    fn nonassoc_leaves_an_error() {
+        let g = grammar("%nonassoc \"<\"\nE -> E \"<\" E | NUMBER ;");
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        assert!(!table.has_conflicts());
+        assert_eq!(table.resolutions().len(), 1);
+        let resolution = &table.resolutions()[0];
+        assert_eq!(resolution.chosen, Error);
+        assert_eq!(table.action(resolution.state, &Term("LT".to_string())), Some(&Error));
+    }
+
+    #[test]
+    fn nonassoc_keeps_reduce_reduce_conflicts() {
+        let g = grammar("%nonassoc \"<\"\nE -> E \"<\" E | E \"<\" E \"<\" E | NUMBER ;");
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        let lt = Term("LT".to_string());
+        assert!(table.conflicts().iter().any(|c| c.symbol == lt));
+        for conflict in table.conflicts().iter() {
+            assert_eq!(conflict.actions.len(), 2);
+            for action in conflict.actions.iter() {
+                match *action {
+                    Reduce(_) => {}
+                    _ => { fail!("{} is not a reduce/reduce conflict", conflict) }
+                }
+            }
+        }
+    }
+}
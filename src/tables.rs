@@ -0,0 +1,247 @@
+// Tim Henderson <tim.tadh@gmail.com>
+// Copyright 2014
+// All rights reserved.
+// For licensing information see the top level directory.
+
+//! A parse table flattened into arrays of numbers, the form the code
+//! generators write out.
+//!
+//! Terminal `i` of `terminals` is column `i + 1` of the action table, column
+//! 0 being the end of the input. An action is
+//!
+//!     0         an error
+//!     s + 1     shift and go to state s
+//!     -(p + 1)  reduce by production p
+//!     ACCEPT    accept
+//!
+//! and a goto is the state to go to, or -1 where there is none. States with
+//! the same actions (or gotos) share a row, so each state is mapped to its
+//! row by `action_rows` (or `goto_rows`).
+
+use std::collections::HashSet;
+use std::i32;
+
+use slr::{Grammar, Production, LRTable, Term, NonTerm, EndOfInput};
+use slr::{Shift, Reduce, Accept, Goto};
+
+pub static ERROR : i32 = 0;
+pub static ACCEPT : i32 = i32::MAX;
+pub static NO_GOTO : i32 = -1;
+
+pub struct Tables<'a> {
+    pub terminals : Vec<String>,
+    pub nonterminals : Vec<String>,
+    pub productions : Vec<&'a Production>,
+    /// The index in `nonterminals` of each production's nonterminal.
+    pub lhs : Vec<uint>,
+    pub starts : Vec<(String, uint)>,
+    pub states : uint,
+    pub action_rows : Vec<uint>,
+    pub actions : Vec<Vec<i32>>,
+    pub goto_rows : Vec<uint>,
+    pub gotos : Vec<Vec<i32>>
+}
+
+impl<'a> Tables<'a> {
+    /// Flattens `table`, which must have been built from `grammar`. Fails
+    /// on an action which refers to a production the grammar does not list.
+    pub fn new(grammar : &'a Grammar, table : &LRTable<'a>) -> Result<Tables<'a>, String> {
+        let terminals = grammar.terminals();
+        let nonterminals = grammar.nonterminals();
+        let productions = grammar.productions();
+        let mut lhs : Vec<uint> = Vec::new();
+        for p in productions.iter() {
+            match nonterminals.iter().position(|name| name == p.nt()) {
+                Some(i) => { lhs.push(i); }
+                None => { return Err(format!("production {} has an unknown nonterminal", p)) }
+            }
+        }
+
+        let mut columns = vec![EndOfInput];
+        columns.extend(terminals.iter().map(|name| Term(name.clone())));
+        let mut action_rows : Vec<uint> = Vec::new();
+        let mut actions : Vec<Vec<i32>> = Vec::new();
+        let mut goto_rows : Vec<uint> = Vec::new();
+        let mut gotos : Vec<Vec<i32>> = Vec::new();
+        for state in range(0, table.len()) {
+            let mut row : Vec<i32> = Vec::new();
+            for sym in columns.iter() {
+                row.push(match table.action(state, sym) {
+                    Some(&Shift(s)) => { (s + 1) as i32 }
+                    Some(&Reduce(p)) => {
+                        match productions.iter().position(|q| **q == *p) {
+                            Some(index) => { -((index + 1) as i32) }
+                            None => {
+                                return Err(format!("state {} reduces on {} by {}, which is not a production of the grammar", state, sym, p))
+                            }
+                        }
+                    }
+                    Some(&Accept) => { ACCEPT }
+                    _ => { ERROR }
+                });
+            }
+            action_rows.push(Tables::share(&mut actions, row));
+            let row : Vec<i32> = nonterminals.iter().map(|name| {
+                match table.action(state, &NonTerm(name.clone())) {
+                    Some(&Goto(s)) => { s as i32 }
+                    _ => { NO_GOTO }
+                }
+            }).collect();
+            goto_rows.push(Tables::share(&mut gotos, row));
+        }
+
+        Ok(Tables{
+            terminals: terminals,
+            nonterminals: nonterminals,
+            productions: productions,
+            lhs: lhs,
+            starts: table.starts().clone(),
+            states: table.len(),
+            action_rows: action_rows,
+            actions: actions,
+            goto_rows: goto_rows,
+            gotos: gotos
+        })
+    }
+
+    /// The index of `row` in `rows`, adding it if it is not there yet.
+    fn share(rows : &mut Vec<Vec<i32>>, row : Vec<i32>) -> uint {
+        match rows.iter().position(|r| *r == row) {
+            Some(i) => { i }
+            None => {
+                rows.push(row);
+                rows.len() - 1
+            }
+        }
+    }
+
+    /// The number of columns of the action table.
+    pub fn columns(&self) -> uint {
+        self.terminals.len() + 1
+    }
+
+    /// The action rows one after another, as the generated array holds them.
+    pub fn action_values(&self) -> Vec<String> {
+        flatten(&self.actions)
+    }
+
+    /// The goto rows one after another, as the generated array holds them.
+    pub fn goto_values(&self) -> Vec<String> {
+        flatten(&self.gotos)
+    }
+}
+
+fn flatten(rows : &Vec<Vec<i32>>) -> Vec<String> {
+    let mut values : Vec<String> = Vec::new();
+    for row in rows.iter() {
+        values.extend(row.iter().map(|v| v.to_string()));
+    }
+    values
+}
+
+/// Identifiers for `names` in generated code. Characters which cannot
+/// appear in one are dropped, except that EBNF suffixes are spelled out, so
+/// `(COMMA Expr)*` becomes `COMMA_Expr_Star`. Names which would then clash,
+/// with each other or with `reserved`, are numbered.
+pub fn identifiers(names : &[String], reserved : &[&str]) -> Vec<String> {
+    let mut taken : HashSet<String> = reserved.iter().map(|word| word.to_string()).collect();
+    let mut idents : Vec<String> = Vec::new();
+    for name in names.iter() {
+        let mut words : Vec<String> = Vec::new();
+        let mut word = String::new();
+        for ch in name.as_slice().chars() {
+            let suffix = match ch {
+                '?' => { Some("Opt") }
+                '*' => { Some("Star") }
+                '+' => { Some("Plus") }
+                _ => { None }
+            };
+            if ch.is_alphanumeric() || ch == '_' {
+                word.push(ch);
+                continue
+            }
+            if word.len() > 0 {
+                words.push(word);
+                word = String::new();
+            }
+            match suffix {
+                Some(s) => { words.push(s.to_string()); }
+                None => {}
+            }
+        }
+        if word.len() > 0 {
+            words.push(word);
+        }
+        let base = if words.len() == 0 { "Anon".to_string() } else { words.connect("_") };
+        let mut ident = base.clone();
+        let mut n = 2u;
+        while taken.contains(&ident) {
+            ident = format!("{}_{}", base, n);
+            n += 1;
+        }
+        taken.insert(ident.clone());
+        idents.push(ident);
+    }
+    idents
+}
+
+#[cfg(test)]
+mod test {
+    use slr::{Automaton, Term, EndOfInput, Shift, Reduce, Accept};
+    use slr::test::{EXPR, grammar};
+    use super::{Tables, ACCEPT, ERROR};
+
+    #[test]
+    fn rows_match_the_table() {
+        let g = grammar(EXPR);
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        let tables = match Tables::new(&g, &table) {
+            Ok(tables) => { tables }
+            Err(err) => { fail!("{}", err) }
+        };
+        assert_eq!(tables.states, table.len());
+        assert_eq!(tables.action_rows.len(), table.len());
+        assert_eq!(tables.goto_rows.len(), table.len());
+        let mut accepts = 0u;
+        for state in range(0, table.len()) {
+            let row = &tables.actions[tables.action_rows[state]];
+            assert_eq!(row.len(), tables.columns());
+            for (column, &value) in row.iter().enumerate() {
+                let sym = if column == 0 { EndOfInput } else { Term(tables.terminals[column - 1].clone()) };
+                match table.action(state, &sym) {
+                    Some(&Shift(s)) => { assert_eq!(value, (s + 1) as i32); }
+                    Some(&Reduce(p)) => { assert!(*tables.productions[(-value - 1) as uint] == *p); }
+                    Some(&Accept) => {
+                        assert_eq!(value, ACCEPT);
+                        accepts += 1;
+                    }
+                    _ => { assert_eq!(value, ERROR); }
+                }
+            }
+        }
+        assert_eq!(accepts, 1);
+    }
+
+    #[test]
+    fn equal_rows_are_shared() {
+        let g = grammar(EXPR);
+        let automaton = g.lr0_automaton();
+        let table = automaton.lalr_table();
+        let tables = Tables::new(&g, &table).unwrap();
+        // the initial state and the one after LPAREN shift alike
+        assert!(tables.actions.len() < tables.states);
+        for (i, row) in tables.actions.iter().enumerate() {
+            assert!(tables.actions.iter().skip(i + 1).all(|other| other != row));
+        }
+    }
+
+    #[test]
+    fn canonical_tables_have_no_augmented_reductions() {
+        let g = grammar(EXPR);
+        let lr1 = g.lr1_automaton();
+        assert!(Tables::new(&g, &lr1.table()).is_ok());
+        let pager = g.minimal_lr1_automaton();
+        assert!(Tables::new(&g, &pager.table()).is_ok());
+    }
+}